@@ -0,0 +1,234 @@
+use crate::components::*;
+use crate::map::{Map, TileType, MAP_HEIGHT, MAP_WIDTH};
+use specs::prelude::*;
+use std::fs;
+use std::io;
+
+const WALL: char = '#';
+const FLOOR: char = '.';
+const DOWN_STAIRS: char = '>';
+const WOODEN_DOOR: char = '+';
+const BARRICADE: char = '=';
+const SHALLOW_WATER: char = ',';
+const DEEP_WATER: char = '~';
+const LAVA: char = '^';
+const CHASM: char = ':';
+const PLAYER_START: char = '@';
+const UNPRINTABLE_GLYPH: char = '*';
+/// Separates the tiles from the legend listing the start and the entities.
+const LEGEND_HEADER: &str = "-- legend --";
+const START_ENTRY: &str = "start";
+
+/// Writes the current map to a plain text file: the terrain, one character per tile, then a
+/// legend giving where the player stands and the glyph, position and name of every entity.
+pub fn export_map(world: &World, path: &str) -> io::Result<()> {
+    let map = world.fetch::<Map>();
+    let positions = world.read_storage::<Position>();
+    let renderables = world.read_storage::<Renderable>();
+    let names = world.read_storage::<Name>();
+    let players = world.read_storage::<Player>();
+
+    let start = (&positions, &players)
+        .join()
+        .map(|(pos, _player)| map.xy_idx(pos.x, pos.y))
+        .next();
+
+    let mut data = (&positions, &renderables, &names)
+        .join()
+        .collect::<Vec<_>>();
+    data.sort_by_key(|d| d.1.index);
+    let entities: Vec<(usize, char, String)> = data
+        .iter()
+        .map(|(pos, render, name)| {
+            (
+                map.xy_idx(pos.x, pos.y),
+                glyph_to_char(render.glyph),
+                name.name.clone(),
+            )
+        })
+        .collect();
+
+    fs::write(path, map_to_ascii(&map, start, &entities))
+}
+
+/// The text `export_map` writes. The legend is only for reading: entities aren't spawned from
+/// it when the map is loaded; `--map` fills the level with random spawns instead unless
+/// `--no-spawns` is given.
+pub fn map_to_ascii(map: &Map, start: Option<usize>, entities: &[(usize, char, String)]) -> String {
+    let mut lines: Vec<String> = map
+        .tiles
+        .chunks(map.width as usize)
+        .map(|row| row.iter().map(tile_to_char).collect())
+        .collect();
+
+    lines.push(LEGEND_HEADER.to_string());
+    if let Some(start) = start {
+        let pos = map.pos_from_idx(start);
+        lines.push(format!("{} {} {}", START_ENTRY, pos.x, pos.y));
+    }
+    for (idx, glyph, name) in entities.iter() {
+        let pos = map.pos_from_idx(*idx);
+        lines.push(format!("{} {} {} {}", glyph, pos.x, pos.y, name));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Loads a map written by `export_map` or drawn by hand.
+pub fn load_map(path: &str, depth: i32) -> io::Result<Map> {
+    let text = fs::read_to_string(path)?;
+    map_from_ascii(&text, depth)
+}
+
+/// Builds a map from text. Rows shorter than the map are padded with walls, and anything that
+/// isn't a tile is an error. The player starts at the legend's `start` entry or, in maps drawn
+/// by hand, at an `@`; with neither, on the first open tile. Only one start is allowed.
+pub fn map_from_ascii(text: &str, depth: i32) -> io::Result<Map> {
+    let rows: Vec<&str> = text
+        .lines()
+        .take_while(|line| *line != LEGEND_HEADER)
+        .collect();
+    let legend: Vec<&str> = text
+        .lines()
+        .skip_while(|line| *line != LEGEND_HEADER)
+        .skip(1)
+        .collect();
+    if rows.len() > MAP_HEIGHT as usize
+        || rows
+            .iter()
+            .any(|row| row.chars().count() > MAP_WIDTH as usize)
+    {
+        return Err(invalid(format!(
+            "Maps can be at most {}x{} tiles",
+            MAP_WIDTH, MAP_HEIGHT
+        )));
+    }
+
+    let mut map = Map::new(depth);
+    let mut starts = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let idx = map.xy_idx(x as i32, y as i32);
+            map.tiles[idx] = char_to_tile(c)
+                .ok_or_else(|| invalid(format!("Unknown tile '{}' at {},{}", c, x, y)))?;
+            if c == PLAYER_START {
+                starts.push(idx);
+            }
+        }
+    }
+    for entry in legend.iter() {
+        let mut fields = entry.split_whitespace();
+        if fields.next() != Some(START_ENTRY) {
+            continue;
+        }
+        let coords: Vec<i32> = fields.filter_map(|field| field.parse().ok()).collect();
+        match coords[..] {
+            [x, y] if map.is_inside_map(x, y) => starts.push(map.xy_idx(x, y)),
+            _ => return Err(invalid(format!("Bad start entry '{}'", entry))),
+        }
+    }
+
+    map.start_idx = match starts[..] {
+        [idx] => idx,
+        [] => map
+            .tiles
+            .iter()
+            .position(|tile| *tile != TileType::Wall)
+            .ok_or_else(|| invalid("Map has no open tiles".to_string()))?,
+        _ => return Err(invalid("Map has more than one start".to_string())),
+    };
+
+    Ok(map)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn tile_to_char(tile: &TileType) -> char {
+    match tile {
+        TileType::Floor => FLOOR,
+        TileType::Wall => WALL,
+        TileType::DownStairs => DOWN_STAIRS,
+        TileType::WoodenDoor => WOODEN_DOOR,
+        TileType::Barricade => BARRICADE,
+        TileType::ShallowWater => SHALLOW_WATER,
+        TileType::DeepWater => DEEP_WATER,
+        TileType::Lava => LAVA,
+        TileType::Chasm => CHASM,
+    }
+}
+
+fn char_to_tile(c: char) -> Option<TileType> {
+    match c {
+        WALL | ' ' => Some(TileType::Wall),
+        FLOOR | PLAYER_START => Some(TileType::Floor),
+        DOWN_STAIRS => Some(TileType::DownStairs),
+        WOODEN_DOOR => Some(TileType::WoodenDoor),
+        BARRICADE => Some(TileType::Barricade),
+        SHALLOW_WATER => Some(TileType::ShallowWater),
+        DEEP_WATER => Some(TileType::DeepWater),
+        LAVA => Some(TileType::Lava),
+        CHASM => Some(TileType::Chasm),
+        _ => None,
+    }
+}
+
+fn glyph_to_char(glyph: u8) -> char {
+    if glyph.is_ascii_graphic() {
+        glyph as char
+    } else {
+        UNPRINTABLE_GLYPH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../maps/fixtures/every_tile.txt");
+
+    #[test]
+    fn fixture_loads() {
+        let map = map_from_ascii(FIXTURE, 1).unwrap();
+        let start = map.pos_from_idx(map.start_idx);
+        assert_eq!((start.x, start.y), (3, 2));
+        assert!(map.tiles[map.xy_idx(10, 2)] == TileType::DownStairs);
+        assert!(map.tiles[map.xy_idx(0, 0)] == TileType::Wall);
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let map = map_from_ascii(FIXTURE, 1).unwrap();
+        let entities = vec![(map.xy_idx(10, 2), 'o', "Orc".to_string())];
+        let text = map_to_ascii(&map, Some(map.start_idx), &entities);
+
+        let reloaded = map_from_ascii(&text, 1).unwrap();
+        assert!(reloaded.tiles == map.tiles);
+        assert_eq!(reloaded.start_idx, map.start_idx);
+    }
+
+    #[test]
+    fn unknown_tiles_are_rejected() {
+        assert!(map_from_ascii("#####\n#.o.#\n#####\n", 1).is_err());
+    }
+
+    #[test]
+    fn only_one_start_is_allowed() {
+        assert!(map_from_ascii("#####\n#@.@#\n#####\n", 1).is_err());
+    }
+
+    #[test]
+    fn starts_off_the_map_are_rejected() {
+        let off_the_edge = format!(
+            "#####\n#...#\n#####\n{}\nstart {} 1\n",
+            LEGEND_HEADER, MAP_WIDTH
+        );
+        let off_the_bottom = format!(
+            "#####\n#...#\n#####\n{}\nstart 1 {}\n",
+            LEGEND_HEADER, MAP_HEIGHT
+        );
+        assert!(map_from_ascii(&off_the_edge, 1).is_err());
+        assert!(map_from_ascii(&off_the_bottom, 1).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{map_themes, spawner, DamageType, InflictsStatus, Resistances};
+use rltk::RGB;
+use serde::Deserialize;
+
+const CAMPAIGN_DATA: &str = include_str!("../data/campaign.json");
+
+/// An innate ability given to the boss, in the same terms as the effect components.
+#[derive(Deserialize, Clone)]
+pub struct AbilityData {
+    pub name: String,
+    pub cooldown: i32,
+    pub range: Option<i32>,
+    pub damage: Option<i32>,
+    /// The type of damage dealt; physical if not given.
+    pub damage_type: Option<DamageType>,
+    pub radius: Option<i32>,
+    pub status: Option<InflictsStatus>,
+    pub heal: Option<i32>,
+}
+
+/// A stage of the boss fight, entered once the boss drops below `below` percent of its health.
+/// The boss gains the extra power, defense and abilities listed.
+#[derive(Deserialize, Clone)]
+pub struct PhaseData {
+    pub below: i32,
+    pub message: String,
+    #[serde(default)]
+    pub power: i32,
+    #[serde(default)]
+    pub defense: i32,
+    #[serde(default)]
+    pub abilities: Vec<AbilityData>,
+}
+
+#[derive(Deserialize)]
+struct AttributesData {
+    might: i32,
+    fitness: i32,
+    quickness: i32,
+    intelligence: i32,
+}
+
+#[derive(Deserialize)]
+struct DiceData {
+    dice: i32,
+    sides: i32,
+}
+
+#[derive(Deserialize)]
+struct BossData {
+    name: String,
+    glyph: char,
+    colour: String,
+    faction: String,
+    max_hp: i32,
+    defense: i32,
+    power: i32,
+    attributes: AttributesData,
+    melee_damage: DiceData,
+    #[serde(default)]
+    resistances: Resistances,
+    #[serde(default)]
+    escort: Vec<String>,
+    #[serde(default)]
+    abilities: Vec<AbilityData>,
+    #[serde(default)]
+    phases: Vec<PhaseData>,
+}
+
+#[derive(Deserialize)]
+struct CampaignData {
+    final_depth: i32,
+    boss: BossData,
+}
+
+/// The monster waiting at the bottom of the dungeon. Killing it wins the game.
+#[derive(Clone)]
+pub struct BossDefinition {
+    pub name: String,
+    pub glyph: u8,
+    pub fg: RGB,
+    pub faction: String,
+    pub max_hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    /// Might, fitness, quickness and intelligence.
+    pub attributes: [i32; 4],
+    /// The dice the boss hits with in melee, and how many sides they have.
+    pub melee_damage: (i32, i32),
+    pub resistances: Resistances,
+    /// Monsters spawned alongside the boss, by name.
+    pub escort: Vec<String>,
+    pub abilities: Vec<AbilityData>,
+    /// The stages of the fight, in the order they're entered.
+    pub phases: Vec<PhaseData>,
+}
+
+/// How deep the dungeon goes and who waits at the bottom, from `data/campaign.json`.
+pub struct Campaign {
+    pub final_depth: i32,
+    pub boss: BossDefinition,
+}
+
+impl Campaign {
+    /// Parses the campaign data, checking the boss's colour against the palette, that its escort
+    /// are monsters the spawner knows, and that its phases come in order.
+    pub fn load() -> Campaign {
+        let data: CampaignData =
+            serde_json::from_str(CAMPAIGN_DATA).expect("Unable to parse campaign.json");
+        let boss = data.boss;
+
+        let fg = map_themes::palette_colour(&boss.colour).unwrap_or_else(|| {
+            panic!(
+                "{}: colour {} is not in palette.css",
+                boss.name, boss.colour
+            )
+        });
+        for monster in boss.escort.iter() {
+            if spawner::monster_spawner(monster).is_none() {
+                panic!("{}: escorted by unknown monster {}", boss.name, monster);
+            }
+        }
+        for pair in boss.phases.windows(2) {
+            if pair[1].below >= pair[0].below {
+                panic!("{}: phases must start at ever lower health", boss.name);
+            }
+        }
+
+        Campaign {
+            final_depth: data.final_depth,
+            boss: BossDefinition {
+                name: boss.name,
+                glyph: rltk::to_cp437(boss.glyph),
+                fg,
+                faction: boss.faction,
+                max_hp: boss.max_hp,
+                defense: boss.defense,
+                power: boss.power,
+                attributes: [
+                    boss.attributes.might,
+                    boss.attributes.fitness,
+                    boss.attributes.quickness,
+                    boss.attributes.intelligence,
+                ],
+                melee_damage: (boss.melee_damage.dice, boss.melee_damage.sides),
+                resistances: boss.resistances,
+                escort: boss.escort,
+                abilities: boss.abilities,
+                phases: boss.phases,
+            },
+        }
+    }
+}
@@ -40,6 +40,8 @@ pub struct Name {
 #[derive(Component, Serialize, Deserialize, Clone, Debug)]
 pub struct BlocksTile {}
 
+/// `power` is added to melee attack rolls; `defense` to the armour class those rolls have to
+/// beat.
 #[derive(Component, ConvertSaveload, Clone, Debug)]
 pub struct CombatStats {
     pub max_hp: i32,
@@ -48,14 +50,92 @@ pub struct CombatStats {
     pub power: i32,
 }
 
+/// Ability scores, 10 being average. Every two points above or below give a +1 or -1 bonus:
+/// might to melee attack and damage rolls, fitness to health, quickness to armour class and
+/// intelligence to the damage done with scrolls and spells.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Attributes {
+    pub might: i32,
+    pub fitness: i32,
+    pub quickness: i32,
+    pub intelligence: i32,
+}
+
+/// How hard a creature hits in melee: `dice` rolls of a `sides`-sided die.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct MeleeDamage {
+    pub dice: i32,
+    pub sides: i32,
+}
+
 #[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct WantsToMelee {
     pub target: Entity,
 }
 
+/// The kinds of harm there are, for creatures to resist or be vulnerable to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum DamageType {
+    Physical,
+    Fire,
+    Cold,
+    Poison,
+    Arcane,
+}
+
+impl DamageType {
+    pub fn name(self) -> &'static str {
+        match self {
+            DamageType::Physical => "physical",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+            DamageType::Poison => "poison",
+            DamageType::Arcane => "arcane",
+        }
+    }
+}
+
+/// Every hit a creature takes this turn, to be applied by the damage system, and whether each was
+/// the player's doing.
 #[derive(Component, ConvertSaveload, Clone, Debug)]
 pub struct SufferDamage {
-    pub amount: i32,
+    pub amounts: Vec<(i32, DamageType, bool)>,
+}
+
+impl SufferDamage {
+    /// Adds a hit to whatever else `victim` is suffering this turn.
+    pub fn new_damage(
+        store: &mut WriteStorage<SufferDamage>,
+        victim: Entity,
+        amount: i32,
+        damage_type: DamageType,
+        by_player: bool,
+    ) {
+        if let Some(suffering) = store.get_mut(victim) {
+            suffering.amounts.push((amount, damage_type, by_player));
+        } else {
+            store
+                .insert(
+                    victim,
+                    SufferDamage {
+                        amounts: vec![(amount, damage_type, by_player)],
+                    },
+                )
+                .expect("Unable to insert damage");
+        }
+    }
+}
+
+/// How a creature takes the different types of damage: half from those it's resistant to, none
+/// from those it's immune to and double from those it's vulnerable to.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Resistances {
+    #[serde(default)]
+    pub resistant: Vec<DamageType>,
+    #[serde(default)]
+    pub immune: Vec<DamageType>,
+    #[serde(default)]
+    pub vulnerable: Vec<DamageType>,
 }
 
 #[derive(Component, Serialize, Deserialize, Clone, Debug)]
@@ -99,6 +179,7 @@ pub struct Ranged {
 #[derive(Component, ConvertSaveload, Clone, Debug)]
 pub struct InflictsDamage {
     pub damage: i32,
+    pub damage_type: DamageType,
 }
 
 #[derive(Component, ConvertSaveload, Clone, Debug)]
@@ -106,11 +187,257 @@ pub struct AreaOfEffect {
     pub radius: i32,
 }
 
+/// The lingering effects a creature can be put under.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatusKind {
+    Confusion,
+    Poison,
+    Regeneration,
+    Haste,
+    Slow,
+    Sleep,
+    Stun,
+    Blindness,
+    Fear,
+}
+
+impl StatusKind {
+    /// How the effect describes whoever is under it.
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusKind::Confusion => "Confused",
+            StatusKind::Poison => "Poisoned",
+            StatusKind::Regeneration => "Regenerating",
+            StatusKind::Haste => "Hasted",
+            StatusKind::Slow => "Slowed",
+            StatusKind::Sleep => "Asleep",
+            StatusKind::Stun => "Stunned",
+            StatusKind::Blindness => "Blinded",
+            StatusKind::Fear => "Afraid",
+        }
+    }
+
+    /// Whether the effect is one to wish on enemies rather than friends.
+    pub fn is_harmful(self) -> bool {
+        match self {
+            StatusKind::Regeneration | StatusKind::Haste => false,
+            _ => true,
+        }
+    }
+
+    /// Whether a creature under the effect can't do anything at all.
+    pub fn incapacitates(self) -> bool {
+        match self {
+            StatusKind::Sleep | StatusKind::Stun => true,
+            _ => false,
+        }
+    }
+}
+
+/// A status effect on `target`, kept as an entity of its own like abilities are. It lasts for
+/// `turns` more turns; `potency` is how much it hurts or heals each turn, for effects that do.
 #[derive(Component, ConvertSaveload, Clone, Debug)]
-pub struct Confusion {
+pub struct StatusEffect {
+    pub target: Entity,
+    pub kind: StatusKind,
+    pub turns: i32,
+    pub potency: i32,
+}
+
+/// Items and abilities with this put their targets under a status effect.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct InflictsStatus {
+    pub kind: StatusKind,
     pub turns: i32,
+    #[serde(default)]
+    pub potency: i32,
 }
 
+/// Items with this component tunnel through walls between the user and the target.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct DigsTerrain {}
+
+/// Tools with this component let their carrier dig through walls by walking into them.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Digger {}
+
+/// Area effects with this component also blow apart weak terrain such as doors and barricades.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct DestroysTerrain {}
+
+/// Items that can be used a limited number of times before they are used up.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Charges {
+    pub remaining: i32,
+}
+
+/// Gear too heavy to swim with; its carrier can't enter deep water.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Heavy {}
+
+/// Set alight by lava. Burns for a few turns, or until doused in water.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Burning {
+    pub turns: i32,
+}
+
+/// Gives off light around its position, or around whoever is carrying it.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct LightSource {
+    pub colour: RGB,
+    pub range: i32,
+}
+
+/// Entities that only last a few turns, like the flash of a fireball.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Lifetime {
+    pub turns: i32,
+}
+
+/// Which side a creature is on. How factions treat each other is set in `data/factions.json`.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Faction {
+    pub name: String,
+}
+
+/// What a monster is up to. An idle monster hasn't noticed any enemy yet; once it has, it hunts
+/// them, unless it's fleeing.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AiState {
+    /// Does nothing at all until a noise wakes it.
+    Asleep,
+    Idle,
+    /// Heading for the spot an enemy was last seen.
+    Hunting { x: i32, y: i32 },
+    /// Poking around the spot where the trail went cold, for a few more turns.
+    Searching { x: i32, y: i32, turns: i32 },
+    /// Running from every enemy and threat in sight until its courage returns.
+    Fleeing,
+}
+
+/// What a monster does while it hasn't noticed any enemy.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum IdleBehaviour {
+    /// Potters about the room its home is in, or near its home on levels without rooms.
+    Wander { x: i32, y: i32 },
+    /// Walks from room to room along a route it picks the first time it sets off.
+    Patrol {
+        waypoints: Vec<(i32, i32)>,
+        next: usize,
+    },
+    /// Keeps to its post, going back there after a chase.
+    Guard { x: i32, y: i32 },
+}
+
+/// Monsters with this component pick up items they come across while idle, and use them.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct PicksUpItems {}
+
+/// The remains of a dead creature, left where it fell.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Corpse {}
+
+/// How hungry a creature is, from just having eaten to wasting away.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum HungerState {
+    WellFed,
+    Normal,
+    Hungry,
+    Starving,
+}
+
+impl HungerState {
+    pub fn name(self) -> &'static str {
+        match self {
+            HungerState::WellFed => "Well Fed",
+            HungerState::Normal => "Normal",
+            HungerState::Hungry => "Hungry",
+            HungerState::Starving => "Starving",
+        }
+    }
+}
+
+/// Counts down to a creature getting hungrier: `turns` more turns in its current state.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct HungerClock {
+    pub state: HungerState,
+    pub turns: i32,
+}
+
+/// Items with this can be eaten, leaving the eater well fed.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct ProvidesFood {}
+
+/// When a monster's nerve breaks: below `flee_below` percent of its health, or as soon as it
+/// sees an enemy if it's `cowardly`. A `shaken` monster stays frightened for that many turns.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Morale {
+    pub flee_below: i32,
+    pub cowardly: bool,
+    pub shaken: i32,
+}
+
+/// Makes it harder for monsters to hear or spot whoever has it.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Stealth {
+    pub rating: i32,
+}
+
+/// Monsters in a pack follow a leader, and scatter when it dies.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct PackMember {
+    pub leader: Entity,
+}
+
+/// An innate attack or spell belonging to a monster. What it does is given by the same effect
+/// components as items, and the owner uses it through `WantsToUseItem`.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Ability {
+    pub owner: Entity,
+    pub cooldown: i32,
+    pub ready_in: i32,
+}
+
+/// The monster at the bottom of the dungeon. `phase` counts how many stages of the fight, as set
+/// out in `data/campaign.json`, it has gone through.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Boss {
+    pub phase: usize,
+}
+
+/// Monsters with this component back away from enemies closer than `distance`.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct KeepsDistance {
+    pub distance: i32,
+}
+
+/// Items with this component win their targets over to the user's faction.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Charm {}
+
+/// How the player's run has gone so far, for the victory screen.
+#[derive(Component, ConvertSaveload, Clone, Debug, Default)]
+pub struct RunStats {
+    pub deepest_level: i32,
+    pub turns: i32,
+    pub kills: i32,
+    pub items_used: i32,
+}
+
+/// A non-hostile character that talks when the player bumps into it.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Npc {
+    pub lines: Vec<String>,
+}
+
+/// Npcs with this component stroll around at random.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Wanderer {}
+
+/// Npcs with this component restore the player's health when talked to.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Healer {}
+
 pub struct SerializeMe;
 
 // Special component that exists to help serialize the game data
@@ -0,0 +1,221 @@
+use crate::{Map, TileType};
+use rltk::BaseMap;
+use std::collections::{HashMap, VecDeque};
+
+/// Distance assigned to tiles that can't be reached from any start.
+pub const UNREACHABLE: f32 = std::f32::MAX;
+
+/// How much further than the threat is "far away" when building a flee map. Above 1.0 so that
+/// fleeing prefers open ground over the nearest corner.
+const FLEE_FACTOR: f32 = 1.2;
+
+/// Extra cost of walking through a tile someone is standing on. Creatures aren't walls: paths can
+/// lead through them, but go around when that isn't much further.
+const OCCUPIED_COST: f32 = 5.0;
+
+/// How many maps `DijkstraCache` holds before it starts over.
+const MAX_CACHED_MAPS: usize = 128;
+
+const NEIGHBOURS: [(i32, i32, f32); 8] = [
+    (-1, 0, 1.0),
+    (1, 0, 1.0),
+    (0, -1, 1.0),
+    (0, 1, 1.0),
+    (-1, -1, 1.45),
+    (1, -1, 1.45),
+    (-1, 1, 1.45),
+    (1, 1, 1.45),
+];
+
+/// A flow field over the map: every tile holds the walking distance to the nearest start, using
+/// the same step costs as `Map::get_available_exits`. Walking downhill leads to a start.
+#[derive(Clone)]
+pub struct DijkstraMap {
+    pub distances: Vec<f32>,
+}
+
+impl DijkstraMap {
+    /// Builds a distance map from any number of starts, which are seeded even if someone stands
+    /// on them. Tiles with a creature on them cost more to cross, and walls are never crossed.
+    pub fn new(map: &Map, starts: &[usize]) -> DijkstraMap {
+        DijkstraMap::from_starts(map, starts, walking_cost)
+    }
+
+    /// Like `new`, but only the terrain counts: creatures are left out, so the map stays right
+    /// however they move.
+    pub fn terrain(map: &Map, starts: &[usize]) -> DijkstraMap {
+        DijkstraMap::from_starts(map, starts, terrain_cost)
+    }
+
+    fn from_starts<F: Fn(&Map, usize) -> Option<f32>>(
+        map: &Map,
+        starts: &[usize],
+        tile_cost: F,
+    ) -> DijkstraMap {
+        let mut distances = vec![UNREACHABLE; map.tiles.len()];
+        let mut open_list = VecDeque::new();
+        for start in starts {
+            distances[*start] = 0.0;
+            open_list.push_back(*start);
+        }
+        scan(map, &mut distances, open_list, tile_cost);
+        DijkstraMap { distances }
+    }
+
+    /// How far a sound made at `origin` carries to every tile. Sound goes around creatures and
+    /// over any terrain, and only solid walls stop it.
+    pub fn sound(map: &Map, origin: usize) -> DijkstraMap {
+        let mut distances = vec![UNREACHABLE; map.tiles.len()];
+        distances[origin] = 0.0;
+        let mut open_list = VecDeque::new();
+        open_list.push_back(origin);
+        scan(map, &mut distances, open_list, |map, idx| {
+            if map.tiles[idx] == TileType::Wall {
+                None
+            } else {
+                Some(1.0)
+            }
+        });
+        DijkstraMap { distances }
+    }
+
+    /// Turns a map leading towards something into one leading away from it: distances are
+    /// inverted so the farthest tiles are the lowest, then rescanned so that walking downhill
+    /// goes around corners instead of into dead ends. Like `terrain`, the rescan leaves creatures
+    /// out.
+    pub fn flee(&self, map: &Map) -> DijkstraMap {
+        let mut distances: Vec<f32> = self
+            .distances
+            .iter()
+            .map(|distance| {
+                if *distance == UNREACHABLE {
+                    UNREACHABLE
+                } else {
+                    -distance * FLEE_FACTOR
+                }
+            })
+            .collect();
+        let open_list = (0..distances.len())
+            .filter(|idx| distances[*idx] < UNREACHABLE)
+            .collect();
+        scan(map, &mut distances, open_list, terrain_cost);
+        DijkstraMap { distances }
+    }
+
+    pub fn is_reachable(&self, idx: usize) -> bool {
+        self.distances[idx] < UNREACHABLE
+    }
+
+    /// The reachable tile farthest from every start.
+    pub fn farthest(&self) -> Option<usize> {
+        self.distances
+            .iter()
+            .enumerate()
+            .filter(|(_idx, distance)| **distance < UNREACHABLE)
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .map(|(idx, _distance)| idx)
+    }
+
+    /// The open neighbour of `idx` with the lowest distance, i.e. one step along the flow.
+    /// Tiles someone is standing on right now aren't open. Returns `None` if there is nowhere
+    /// lower to go.
+    pub fn downhill_step(&self, map: &Map, idx: usize) -> Option<usize> {
+        let current = self.distances[idx];
+        map.get_available_exits(idx)
+            .iter()
+            .map(|(exit, _cost)| *exit)
+            .filter(|exit| self.distances[*exit] < current)
+            .min_by(|a, b| self.distances[*a].partial_cmp(&self.distances[*b]).unwrap())
+    }
+}
+
+/// What it costs to walk onto a tile, or `None` if the terrain can't be walked onto. Any other
+/// tile that's blocked has a creature on it.
+fn walking_cost(map: &Map, idx: usize) -> Option<f32> {
+    let terrain_cost = terrain_cost(map, idx)?;
+    if map.blocked_tiles[idx] {
+        Some(terrain_cost + OCCUPIED_COST)
+    } else {
+        Some(terrain_cost)
+    }
+}
+
+fn terrain_cost(map: &Map, idx: usize) -> Option<f32> {
+    map.tiles[idx].movement_cost()
+}
+
+/// Relaxes distances outwards from the open list until nothing improves. `tile_cost` gives the
+/// cost of entering a tile, with `None` for tiles that can't be entered.
+fn scan<F: Fn(&Map, usize) -> Option<f32>>(
+    map: &Map,
+    distances: &mut Vec<f32>,
+    mut open_list: VecDeque<usize>,
+    tile_cost: F,
+) {
+    while let Some(idx) = open_list.pop_front() {
+        let pos = map.pos_from_idx(idx);
+        for (dx, dy, cost) in NEIGHBOURS.iter() {
+            let (x, y) = (pos.x + dx, pos.y + dy);
+            if x < 0 || x >= map.width || y < 0 || y >= map.height {
+                continue;
+            }
+            let neighbour = map.xy_idx(x, y);
+            let terrain_cost = match tile_cost(map, neighbour) {
+                Some(terrain_cost) => terrain_cost,
+                None => continue,
+            };
+            let distance = distances[idx] + cost * terrain_cost;
+            if distance < distances[neighbour] {
+                distances[neighbour] = distance;
+                open_list.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Distance maps shared by everything that needs them, so a pack of monsters chasing the player
+/// builds one map between them. The maps only follow the terrain, with creatures left to
+/// `downhill_step`, so they are kept until the terrain changes.
+#[derive(Default)]
+pub struct DijkstraCache {
+    tiles: Vec<TileType>,
+    towards: HashMap<Vec<usize>, DijkstraMap>,
+    away_from: HashMap<Vec<usize>, DijkstraMap>,
+}
+
+impl DijkstraCache {
+    pub fn new() -> DijkstraCache {
+        DijkstraCache {
+            tiles: Vec::new(),
+            towards: HashMap::new(),
+            away_from: HashMap::new(),
+        }
+    }
+
+    /// A map leading to the nearest of `targets`.
+    pub fn towards(&mut self, map: &Map, targets: &[usize]) -> &DijkstraMap {
+        self.invalidate_if_changed(map);
+        self.towards
+            .entry(targets.to_vec())
+            .or_insert_with(|| DijkstraMap::terrain(map, targets))
+    }
+
+    /// A map leading away from all of `threats`.
+    pub fn away_from(&mut self, map: &Map, threats: &[usize]) -> &DijkstraMap {
+        self.invalidate_if_changed(map);
+        if !self.away_from.contains_key(threats) {
+            let flee = self.towards(map, threats).flee(map);
+            self.away_from.insert(threats.to_vec(), flee);
+        }
+        &self.away_from[threats]
+    }
+
+    fn invalidate_if_changed(&mut self, map: &Map) {
+        let full = self.towards.len() + self.away_from.len() >= MAX_CACHED_MAPS;
+        if full || self.tiles != map.tiles {
+            self.tiles = map.tiles.clone();
+            self.towards.clear();
+            self.away_from.clear();
+        }
+    }
+}
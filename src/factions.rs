@@ -0,0 +1,46 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const FACTION_DATA: &str = include_str!("../data/factions.json");
+
+/// The faction the player belongs to. Charmed creatures join it.
+pub const PLAYER_FACTION: &str = "Player";
+
+/// How members of one faction treat members of another.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Reaction {
+    Attack,
+    Ignore,
+    Flee,
+}
+
+/// Every faction's reactions to the others, from `data/factions.json`. Pairs that aren't listed
+/// ignore each other.
+pub struct FactionTable {
+    reactions: HashMap<String, HashMap<String, Reaction>>,
+}
+
+impl FactionTable {
+    /// Parses the faction data, checking that every reaction refers to a faction that exists.
+    pub fn load() -> FactionTable {
+        let reactions: HashMap<String, HashMap<String, Reaction>> =
+            serde_json::from_str(FACTION_DATA).expect("Unable to parse factions.json");
+        for (faction, towards) in reactions.iter() {
+            for other in towards.keys() {
+                if !reactions.contains_key(other) {
+                    panic!("{}: reaction to unknown faction {}", faction, other);
+                }
+            }
+        }
+
+        FactionTable { reactions }
+    }
+
+    pub fn reaction(&self, faction: &str, other: &str) -> Reaction {
+        self.reactions
+            .get(faction)
+            .and_then(|towards| towards.get(other))
+            .cloned()
+            .unwrap_or(Reaction::Ignore)
+    }
+}
@@ -0,0 +1,45 @@
+use rltk::Point;
+
+/// Returns the points on a straight line from `start` to `end`, both included (Bresenham).
+pub fn line(start: Point, end: Point) -> Vec<Point> {
+    let mut points = Vec::new();
+    let dx = (end.x - start.x).abs();
+    let dy = -(end.y - start.y).abs();
+    let step_x = if start.x < end.x { 1 } else { -1 };
+    let step_y = if start.y < end.y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut current = start;
+
+    loop {
+        points.push(current);
+        if current == end {
+            break;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            current.x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            current.y += step_y;
+        }
+    }
+
+    points
+}
+
+/// Extends the line from `start` through `towards` until it is `length` tiles long.
+pub fn extend_to(start: Point, towards: Point, length: i32) -> Point {
+    let dx = (towards.x - start.x) as f32;
+    let dy = (towards.y - start.y) as f32;
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance == 0.0 {
+        return start;
+    }
+    let scale = length as f32 / distance;
+    Point::new(
+        start.x + (dx * scale).round() as i32,
+        start.y + (dy * scale).round() as i32,
+    )
+}
@@ -1,5 +1,9 @@
+use crate::campaign::Campaign;
+use crate::map_themes::Themes;
+use crate::systems::status_effects::{self, Afflictions};
 use crate::{
-    CombatStats, GameLog, InBackpack, Map, Name, Player, Position, RunState, State, Viewshed,
+    CombatStats, GameLog, HungerClock, HungerState, InBackpack, Map, Name, Player, Position,
+    RunState, RunStats, State, StatusEffect, Viewshed,
 };
 use rltk::{Console, Point, Rltk, VirtualKeyCode, RGB};
 use specs::prelude::*;
@@ -17,6 +21,12 @@ pub enum MainMenuResult {
     Selected(MainMenuSelection),
 }
 
+#[derive(PartialEq, Copy, Clone)]
+pub enum VictoryResult {
+    NoResponse,
+    Done,
+}
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum ItemMenuResult {
     Cancel,
@@ -108,6 +118,57 @@ pub fn main_menu(runstate: &RunState, ctx: &mut Rltk) -> MainMenuResult {
     MainMenuResult::NoSelection(MainMenuSelection::NewGame)
 }
 
+/// Congratulates the player on killing the boss and sums up their run. Any key ends the game.
+pub fn victory_screen(world: &World, ctx: &mut Rltk) -> VictoryResult {
+    let campaign = world.fetch::<Campaign>();
+    let player_entity = world.fetch::<Entity>();
+    let run_stats = world.read_storage::<RunStats>();
+    let stats = run_stats.get(*player_entity).cloned().unwrap_or_default();
+
+    ctx.print_color_centered(
+        15,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "You are victorious!",
+    );
+    ctx.print_color_centered(
+        17,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        &format!(
+            "The {} lies dead at the bottom of the dungeon.",
+            campaign.boss.name
+        ),
+    );
+
+    let lines = [
+        format!("Deepest level: {}", stats.deepest_level),
+        format!("Turns taken: {}", stats.turns),
+        format!("Monsters slain: {}", stats.kills),
+        format!("Items used: {}", stats.items_used),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        ctx.print_color_centered(
+            20 + i as i32,
+            RGB::named(rltk::CYAN),
+            RGB::named(rltk::BLACK),
+            line,
+        );
+    }
+
+    ctx.print_color_centered(
+        26,
+        RGB::named(rltk::MAGENTA),
+        RGB::named(rltk::BLACK),
+        "Press any key to quit.",
+    );
+
+    match ctx.key {
+        None => VictoryResult::NoResponse,
+        Some(_) => VictoryResult::Done,
+    }
+}
+
 pub fn draw_ui(world: &World, ctx: &mut Rltk) {
     ctx.draw_box(
         0,
@@ -134,12 +195,20 @@ pub fn draw_ui(world: &World, ctx: &mut Rltk) {
     );
 
     let map = world.fetch::<Map>();
-    let depth = format!("Level: {}", map.depth);
+    let theme = world.fetch::<Themes>();
+    let depth = if map.depth == crate::map_builders::TOWN_DEPTH {
+        "Town".to_string()
+    } else {
+        format!("Level: {} ({})", map.depth, theme.for_depth(map.depth).name)
+    };
     ctx.print_color(2, 43, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &depth);
 
     let combat_stats = world.read_storage::<CombatStats>();
     let players = world.read_storage::<Player>();
-    for (_player, stats) in (&players, &combat_stats).join() {
+    let hunger_clocks = world.read_storage::<HungerClock>();
+    let entities = world.entities();
+    let afflictions = status_effects::afflictions(&world.read_storage::<StatusEffect>());
+    for (player, _player, stats) in (&entities, &players, &combat_stats).join() {
         let health = format!(" HP: {} / {} ", stats.hp, stats.max_hp);
         ctx.print_color(
             12,
@@ -167,15 +236,50 @@ pub fn draw_ui(world: &World, ctx: &mut Rltk) {
             }
             y += 1;
         }
+
+        // How hungry the player is, once it's worth mentioning, and what they're under go along
+        // the bottom of the box, as many as fit
+        let mut statuses = Vec::new();
+        let hunger = hunger_clocks.get(player).map(|clock| clock.state);
+        let hunger_colour = match hunger {
+            Some(HungerState::WellFed) => Some(RGB::named(rltk::GREEN)),
+            Some(HungerState::Hungry) => Some(RGB::named(rltk::ORANGE)),
+            Some(HungerState::Starving) => Some(RGB::named(rltk::RED)),
+            _ => None,
+        };
+        if let (Some(hunger), Some(colour)) = (hunger, hunger_colour) {
+            statuses.push((format!(" {} ", hunger.name()), colour));
+        }
+        let player_effects = afflictions
+            .get(&player)
+            .map_or(&[][..], |effects| &effects[..]);
+        for (kind, turns) in player_effects {
+            let colour = if kind.is_harmful() {
+                RGB::named(rltk::ORANGE)
+            } else {
+                RGB::named(rltk::GREEN)
+            };
+            statuses.push((format!(" {} ({}) ", kind.name(), turns), colour));
+        }
+
+        let mut x = 2;
+        for (status, colour) in statuses {
+            if x + status.len() as i32 > 79 {
+                break;
+            }
+            ctx.print_color(x, 49, colour, RGB::named(rltk::BLACK), &status);
+            x += status.len() as i32;
+        }
     }
 
-    draw_tooltips(world, ctx);
+    draw_tooltips(world, ctx, &afflictions);
 }
 
-fn draw_tooltips(world: &World, ctx: &mut Rltk) {
+fn draw_tooltips(world: &World, ctx: &mut Rltk, afflictions: &Afflictions) {
     let map = world.fetch::<Map>();
     let names = world.read_storage::<Name>();
     let positions = world.read_storage::<Position>();
+    let entities = world.entities();
 
     let mouse_pos = ctx.mouse_pos();
     if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
@@ -191,9 +295,19 @@ fn draw_tooltips(world: &World, ctx: &mut Rltk) {
     );
 
     let mut tooltip: Vec<String> = Vec::new();
-    for (name, position) in (&names, &positions).join() {
+    if !map.can_make_out(idx, *world.fetch::<Point>()) {
+        return;
+    }
+    for (entity, name, position) in (&entities, &names, &positions).join() {
         if position.x == mouse_pos.0 && position.y == mouse_pos.1 {
-            tooltip.push(name.name.to_string());
+            let statuses: Vec<&str> = afflictions.get(&entity).map_or(Vec::new(), |effects| {
+                effects.iter().map(|(kind, _turns)| kind.name()).collect()
+            });
+            if statuses.is_empty() {
+                tooltip.push(name.name.to_string());
+            } else {
+                tooltip.push(format!("{} ({})", name.name, statuses.join(", ")));
+            }
         }
     }
 
@@ -0,0 +1,60 @@
+use crate::spawner;
+use rltk::RandomNumberGenerator;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const LOOT_DATA: &str = include_str!("../data/loot.json");
+
+/// An item with a `chance` in a hundred of dropping.
+#[derive(Deserialize)]
+pub struct LootDrop {
+    pub item: String,
+    pub chance: i32,
+}
+
+/// What a kind of monster leaves behind when it dies.
+#[derive(Deserialize)]
+pub struct LootTable {
+    #[serde(default)]
+    pub corpse: bool,
+    #[serde(default)]
+    pub drops: Vec<LootDrop>,
+}
+
+impl LootTable {
+    /// Rolls for every drop separately, returning the names of the items that dropped.
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> Vec<String> {
+        self.drops
+            .iter()
+            .filter(|drop| rng.roll_dice(1, 100) <= drop.chance)
+            .map(|drop| drop.item.clone())
+            .collect()
+    }
+}
+
+/// Every monster's loot table, from `data/loot.json`, keyed by the monster's name. Monsters that
+/// aren't listed leave nothing behind.
+pub struct LootTables {
+    tables: HashMap<String, LootTable>,
+}
+
+impl LootTables {
+    /// Parses the loot data, checking that every drop is an item the spawner knows how to make.
+    pub fn load() -> LootTables {
+        let tables: HashMap<String, LootTable> =
+            serde_json::from_str(LOOT_DATA).expect("Unable to parse loot.json");
+        for (monster, table) in tables.iter() {
+            for drop in table.drops.iter() {
+                if spawner::item_spawner(&drop.item).is_none() {
+                    panic!("{}: drops unknown item {}", monster, drop.item);
+                }
+            }
+        }
+
+        LootTables { tables }
+    }
+
+    pub fn get(&self, monster: &str) -> Option<&LootTable> {
+        self.tables.get(monster)
+    }
+}
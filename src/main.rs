@@ -16,7 +16,17 @@ mod rect;
 use rect::*;
 mod gamelog;
 use gamelog::*;
+mod ascii_map;
+mod campaign;
+mod dijkstra;
+mod factions;
+mod geometry;
 mod gui;
+mod loot;
+mod map_builders;
+mod map_image;
+mod map_themes;
+mod noise;
 mod spawner;
 mod systems;
 mod save_load;
@@ -33,6 +43,8 @@ pub enum RunState {
     MainMenu(gui::MainMenuSelection),
     SaveGame,
     NextLevel,
+    FallToNextLevel,
+    Victory,
 }
 
 pub struct State {
@@ -60,6 +72,11 @@ impl GameState for State {
                     },
                 }
             }
+            RunState::Victory => match gui::victory_screen(&self.world, ctx) {
+                gui::VictoryResult::NoResponse => RunState::Victory,
+                // The world is what the run left behind, so there's no new game to start from it
+                gui::VictoryResult::Done => ::std::process::exit(0),
+            },
             _ => self.game_tick(ctx),
         };
 
@@ -74,15 +91,50 @@ impl State {
     }
 
     fn game_tick(&mut self, ctx: &mut Rltk) -> RunState {
+        let boss_slain = self.boss_slain();
         systems::damage::delete_the_dead(&mut self.world);
+        if boss_slain {
+            return RunState::Victory;
+        }
         self.process_map(ctx);
         gui::draw_ui(&self.world, ctx);
         self.run_systems_and_process_state(ctx)
     }
 
+    /// Asleep or stunned, the player's turns pass without them.
+    fn player_is_helpless(&self) -> bool {
+        let player_entity = *self.world.fetch::<Entity>();
+        let effects = self.world.read_storage::<StatusEffect>();
+        let afflictions = systems::status_effects::afflictions(&effects);
+        systems::status_effects::is_helpless(&afflictions, player_entity)
+    }
+
+    fn player_status_turns(&self, kind: StatusKind) -> Option<i32> {
+        let player_entity = *self.world.fetch::<Entity>();
+        let effects = self.world.read_storage::<StatusEffect>();
+        let afflictions = systems::status_effects::afflictions(&effects);
+        systems::status_effects::turns_under(&afflictions, player_entity, kind)
+    }
+
+    /// How many turns the player has taken this run.
+    fn player_turns(&self) -> i32 {
+        let player_entity = *self.world.fetch::<Entity>();
+        let run_stats = self.world.read_storage::<RunStats>();
+        run_stats
+            .get(player_entity)
+            .map_or(0, |run_stats| run_stats.turns)
+    }
+
+    fn boss_slain(&self) -> bool {
+        let bosses = self.world.read_storage::<Boss>();
+        let combat_stats = self.world.read_storage::<CombatStats>();
+        (&bosses, &combat_stats).join().any(|(_boss, stats)| stats.hp < 1)
+    }
+
     fn process_map(&mut self, ctx: &mut Rltk) {
         let map = self.world.fetch::<Map>();
-        map.draw(ctx);
+        let themes = self.world.fetch::<map_themes::Themes>();
+        map.draw(ctx, themes.for_depth(map.depth));
 
         let positions = self.world.read_storage::<Position>();
         let renderables = self.world.read_storage::<Renderable>();
@@ -90,9 +142,10 @@ impl State {
         let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
         data.sort_by(|&a, &b| a.1.index.cmp(&b.1.index));
 
+        let player_position = *self.world.fetch::<Point>();
         for (pos, render) in data.iter() {
             let idx = map.xy_idx(pos.x, pos.y);
-            if map.visible_tiles[idx] {
+            if map.can_make_out(idx, player_position) {
                 ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
             }
         }
@@ -104,14 +157,20 @@ impl State {
 
         match run_state {
             RunState::MainMenu(_) => RunState::AwaitingInput,
+            RunState::Victory => RunState::Victory,
             RunState::NextLevel => {
                 self.goto_next_level();
                 RunState::PreRun
             }
+            RunState::FallToNextLevel => {
+                self.fall_to_next_level();
+                RunState::PreRun
+            }
             RunState::SaveGame => {
                 save_load::save_game(&mut self.world);
                 RunState::MainMenu(gui::MainMenuSelection::LoadGame)
             }
+            RunState::AwaitingInput if self.player_is_helpless() => RunState::PlayerTurn,
             RunState::AwaitingInput => {
                 let player_position = *self.world.fetch::<Point>();
                 gui::show_path(&map, &player_position, ctx);
@@ -123,10 +182,29 @@ impl State {
             }
             RunState::PlayerTurn => {
                 self.run_systems();
-                RunState::MonsterTurn
+                let player_entity = *self.world.fetch::<Entity>();
+                let mut run_stats = self.world.write_storage::<RunStats>();
+                let turns = match run_stats.get_mut(player_entity) {
+                    Some(run_stats) => {
+                        run_stats.turns += 1;
+                        run_stats.turns
+                    }
+                    None => 0,
+                };
+                // A hasted player gets every other turn without the monsters moving
+                if turns % 2 == 1 && self.player_status_turns(StatusKind::Haste).is_some() {
+                    RunState::AwaitingInput
+                } else {
+                    RunState::MonsterTurn
+                }
             }
             RunState::MonsterTurn => {
                 self.run_systems();
+                // ...and a slowed one lets the monsters go twice every other turn
+                let slowed = self.player_status_turns(StatusKind::Slow).is_some();
+                if slowed && self.player_turns() % 2 == 1 {
+                    self.run_systems();
+                }
                 RunState::AwaitingInput
             }
             RunState::ShowTargeting { range, item } => match gui::ranged_target(self, ctx, range) {
@@ -192,47 +270,103 @@ impl State {
     fn run_systems(&mut self) {
         let mut map_indexing = systems::MapIndexing {};
         map_indexing.run_now(&self.world);
+        let mut lighting = systems::Lighting {};
+        lighting.run_now(&self.world);
         let mut visibility = systems::Visibility {};
         visibility.run_now(&self.world);
         let mut melee_combat = systems::MeleeCombat {};
         melee_combat.run_now(&self.world);
+        let mut terrain_effects = systems::TerrainEffects {};
+        terrain_effects.run_now(&self.world);
+        let mut status_effects = systems::StatusEffects {};
+        status_effects.run_now(&self.world);
+        let mut hunger = systems::Hunger {};
+        hunger.run_now(&self.world);
         let mut damage = systems::Damage {};
         damage.run_now(&self.world);
+        let mut boss_phases = systems::BossPhases {};
+        boss_phases.run_now(&self.world);
         let mut pickup = systems::Inventory {};
         pickup.run_now(&self.world);
         let mut item_usage = systems::ItemUsage {};
         item_usage.run_now(&self.world);
         let mut item_drop = systems::ItemDrop {};
         item_drop.run_now(&self.world);
+        let mut awareness = systems::Awareness {};
+        awareness.run_now(&self.world);
+        let mut morale_check = systems::MoraleCheck {};
+        morale_check.run_now(&self.world);
         let mut monster_ai = systems::MonsterAI {};
         monster_ai.run_now(&self.world);
+        let mut idle_ai = systems::IdleAI {};
+        idle_ai.run_now(&self.world);
+        let mut npc_ai = systems::NpcAI {};
+        npc_ai.run_now(&self.world);
+        let mut fog_of_war = systems::FogOfWar {};
+        fog_of_war.run_now(&self.world);
         self.world.maintain();
     }
 
     fn goto_next_level(&mut self) {
+        self.change_level();
+
+        // Notify the player and give them some health
+        let player_entity = self.world.fetch::<Entity>();
+        let mut gamelog = self.world.fetch_mut::<gamelog::GameLog>();
+        gamelog.entries.push("You descend to the next level, and take a moment to heal.".to_string());
+        let mut player_health_store = self.world.write_storage::<CombatStats>();
+        let player_health = player_health_store.get_mut(*player_entity);
+        if let Some(player_health) = player_health {
+            player_health.hp = player_health.max_hp;
+        }
+    }
+
+    /// Stepping into a chasm drops the player to the next level the hard way.
+    fn fall_to_next_level(&mut self) {
+        self.change_level();
+
+        let damage = self.world.write_resource::<rltk::RandomNumberGenerator>().roll_dice(2, 4);
+        let player_entity = self.world.fetch::<Entity>();
+        let mut gamelog = self.world.fetch_mut::<gamelog::GameLog>();
+        gamelog.entries.push(format!("You fall into the chasm and land hard, for {} hp.", damage));
+        let mut player_health_store = self.world.write_storage::<CombatStats>();
+        if let Some(player_health) = player_health_store.get_mut(*player_entity) {
+            player_health.hp = i32::max(1, player_health.hp - damage);
+        }
+    }
+
+    fn change_level(&mut self) {
         // Delete entities that aren't the player or his/her equipment
         let to_delete = self.entities_to_remove_on_level_change();
         for target in to_delete {
             self.world.delete_entity(target).expect("Unable to delete entity");
         }
 
+        // Noises made on the old level can't be heard on the new one
+        self.world.write_resource::<noise::Noises>().pending.clear();
+
         // Build a new map and place the player
         let map;
         {
             let mut map_resource = self.world.write_resource::<Map>();
             let new_depth = map_resource.depth + 1;
+            let final_depth = self.world.fetch::<campaign::Campaign>().final_depth;
             let mut new_rng = rltk::RandomNumberGenerator::new(); // TODO: seed strategy
-            *map_resource = Map::new_rooms_and_corridors(&mut new_rng, new_depth);
+            *map_resource = map_builders::build_level(&mut new_rng, new_depth, final_depth);
             map = map_resource.clone();
         }
 
         // Spawn bad guys
-        for room in map.rooms.iter().skip(1) {
-            spawner::spawn_room(&mut self.world, room, &map);
+        spawner::spawn_map(&mut self.world, &map);
+
+        let player_entity = *self.world.fetch::<Entity>();
+        let mut run_stats = self.world.write_storage::<RunStats>();
+        if let Some(run_stats) = run_stats.get_mut(player_entity) {
+            run_stats.deepest_level = i32::max(run_stats.deepest_level, map.depth);
         }
 
         // Place the player and update resources
-        let player_position = map.rooms[0].center();
+        let player_position = map.start_position();
         let mut player_point = self.world.write_resource::<Point>();
         *player_point = Point::new(player_position.x, player_position.y);
         let mut position_components = self.world.write_storage::<Position>();
@@ -249,21 +383,13 @@ impl State {
         if let Some(vs) = vs {
             vs.dirty = true;
         }
-
-        // Notify the player and give them some health
-        let mut gamelog = self.world.fetch_mut::<gamelog::GameLog>();
-        gamelog.entries.push("You descend to the next level, and take a moment to heal.".to_string());
-        let mut player_health_store = self.world.write_storage::<CombatStats>();
-        let player_health = player_health_store.get_mut(*player_entity);
-        if let Some(player_health) = player_health {
-            player_health.hp = player_health.max_hp;
-        }
     }
 
     fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
         let entities = self.world.entities();
         let player = self.world.read_storage::<Player>();
         let backpack = self.world.read_storage::<InBackpack>();
+        let effects = self.world.read_storage::<StatusEffect>();
         let player_entity = self.world.fetch::<Entity>();
 
         let mut to_delete : Vec<Entity> = Vec::new();
@@ -284,6 +410,13 @@ impl State {
                 }
             }
 
+            // Or whatever the player is under
+            if let Some(effect) = effects.get(entity) {
+                if effect.target == *player_entity {
+                    should_delete = false;
+                }
+            }
+
             if should_delete {
                 to_delete.push(entity);
             }
@@ -293,55 +426,135 @@ impl State {
     }
 }
 
+pub fn register_components(world: &mut World) {
+    world.register::<Position>();
+    world.register::<Renderable>();
+    world.register::<Player>();
+    world.register::<Monster>();
+    world.register::<Name>();
+    world.register::<Viewshed>();
+    world.register::<BlocksTile>();
+    world.register::<CombatStats>();
+    world.register::<WantsToMelee>();
+    world.register::<SufferDamage>();
+    world.register::<Item>();
+    world.register::<ProvidesHealing>();
+    world.register::<WantsToPickupItem>();
+    world.register::<InBackpack>();
+    world.register::<WantsToUseItem>();
+    world.register::<WantsToDropItem>();
+    world.register::<Consumable>();
+    world.register::<ProvidesHealing>();
+    world.register::<Ranged>();
+    world.register::<InflictsDamage>();
+    world.register::<AreaOfEffect>();
+    world.register::<StatusEffect>();
+    world.register::<InflictsStatus>();
+    world.register::<HungerClock>();
+    world.register::<ProvidesFood>();
+    world.register::<Npc>();
+    world.register::<Wanderer>();
+    world.register::<Healer>();
+    world.register::<DigsTerrain>();
+    world.register::<Digger>();
+    world.register::<DestroysTerrain>();
+    world.register::<Charges>();
+    world.register::<Heavy>();
+    world.register::<Burning>();
+    world.register::<LightSource>();
+    world.register::<Lifetime>();
+    world.register::<Faction>();
+    world.register::<Charm>();
+    world.register::<AiState>();
+    world.register::<Ability>();
+    world.register::<KeepsDistance>();
+    world.register::<Morale>();
+    world.register::<PackMember>();
+    world.register::<Stealth>();
+    world.register::<IdleBehaviour>();
+    world.register::<PicksUpItems>();
+    world.register::<Corpse>();
+    world.register::<Boss>();
+    world.register::<RunStats>();
+    world.register::<Attributes>();
+    world.register::<MeleeDamage>();
+    world.register::<Resistances>();
+    world.register::<SerializationHelper>();
+    world.register::<SimpleMarker<SerializeMe>>();
+}
+
+/// Returns the value following a command line flag, e.g. `--seed 42`.
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .map(|i| args.get(i + 1).unwrap_or_else(|| panic!("{} needs a value", flag)))
+}
+
 fn main() {
     let mut gs = State {
         world: World::new(),
     };
-    gs.world.register::<Position>();
-    gs.world.register::<Renderable>();
-    gs.world.register::<Player>();
-    gs.world.register::<Monster>();
-    gs.world.register::<Name>();
-    gs.world.register::<Viewshed>();
-    gs.world.register::<BlocksTile>();
-    gs.world.register::<CombatStats>();
-    gs.world.register::<WantsToMelee>();
-    gs.world.register::<SufferDamage>();
-    gs.world.register::<Item>();
-    gs.world.register::<ProvidesHealing>();
-    gs.world.register::<WantsToPickupItem>();
-    gs.world.register::<InBackpack>();
-    gs.world.register::<WantsToUseItem>();
-    gs.world.register::<WantsToDropItem>();
-    gs.world.register::<Consumable>();
-    gs.world.register::<ProvidesHealing>();
-    gs.world.register::<Ranged>();
-    gs.world.register::<InflictsDamage>();
-    gs.world.register::<AreaOfEffect>();
-    gs.world.register::<Confusion>();
-    gs.world.register::<SerializationHelper>();
-    gs.world.register::<SimpleMarker<SerializeMe>>();
-
-    let seed: u64 = 25021990;
+    register_components(&mut gs.world);
+
+    let args: Vec<String> = std::env::args().collect();
+    let seed: u64 = arg_value(&args, "--seed")
+        .map(|seed| seed.parse().expect("--seed must be a number"))
+        .unwrap_or(25021990);
+
+    // Headless mode: draw a level to an image and exit without opening a window. Only the town
+    // is the one a game with the same seed starts in; deeper levels are built fresh each time the
+    // player goes down, so any other depth shows what the seed builds there, not what a game gets.
+    if let Some(path) = arg_value(&args, "--render-png") {
+        let depth = arg_value(&args, "--depth")
+            .map(|depth| depth.parse().expect("--depth must be a number"))
+            .unwrap_or(map_builders::TOWN_DEPTH);
+        map_image::render_level(seed, depth, path).expect("Unable to render map");
+        println!("level {} of seed {} written to {}", depth, seed, path);
+        if depth != map_builders::TOWN_DEPTH {
+            println!("note: games only build the town from the seed, so deeper levels won't match");
+        }
+        return;
+    }
+
     let mut rng = rltk::RandomNumberGenerator::seeded(seed);
     println!("generating world seed {}", seed);
 
-    let map = Map::new_rooms_and_corridors(&mut rng, 1);
+    let campaign = campaign::Campaign::load();
+    let map = match arg_value(&args, "--map") {
+        Some(path) => ascii_map::load_map(path, 1).expect("Unable to load map file"),
+        None => map_builders::build_level(
+            &mut rng,
+            map_builders::TOWN_DEPTH,
+            campaign.final_depth,
+        ),
+    };
 
-    let initial_player_pos = map.rooms[0].center();
+    let initial_player_pos = map.start_position();
 
     gs.world.insert(SimpleMarkerAllocator::<SerializeMe>::new());
     gs.world.insert(rng);
+    gs.world.insert(campaign);
 
-    spawner::spawn_map_rooms(&mut gs.world, &map);
+    // A loaded map is populated like any other level unless asked to stay empty
+    if !args.iter().any(|arg| arg == "--no-spawns") {
+        spawner::spawn_map(&mut gs.world, &map);
+    }
 
     gs.world
         .insert(Point::new(initial_player_pos.x, initial_player_pos.y));
     let player_entity = spawner::player(&mut gs.world, initial_player_pos);
+    if let Some(run_stats) = gs.world.write_storage::<RunStats>().get_mut(player_entity) {
+        run_stats.deepest_level = map.depth;
+    }
 
     let initial_state = RunState::MainMenu(gui::MainMenuSelection::NewGame);
 
     gs.world.insert(map);
+    gs.world.insert(map_themes::Themes::load());
+    gs.world.insert(dijkstra::DijkstraCache::new());
+    gs.world.insert(factions::FactionTable::load());
+    gs.world.insert(loot::LootTables::load());
+    gs.world.insert(noise::Noises::new());
     gs.world.insert(player_entity);
     gs.world.insert(initial_state);
     gs.world.insert(gamelog::GameLog {
@@ -1,7 +1,11 @@
+use super::dijkstra::DijkstraMap;
+use super::map_themes::Theme;
 use super::Position;
 use super::Rect;
+use super::Viewshed;
 use rltk::{Algorithm2D, BaseMap, Console, Point, RandomNumberGenerator, Rltk, RGB};
 use serde::{Deserialize, Serialize};
+use specs::prelude::*;
 use specs::Entity;
 use std::cmp::{max, min};
 
@@ -11,12 +15,87 @@ pub const MAX_ROOMS: usize = 6;
 pub const MAP_SIZE: usize = (MAP_WIDTH * MAP_HEIGHT) as usize;
 pub const MIN_ROOM_SIZE: i32 = 3;
 pub const MAX_ROOM_SIZE: i32 = 6;
+pub const FULL_LIGHT: RGB = RGB {
+    r: 1.0,
+    g: 1.0,
+    b: 1.0,
+};
+/// Tiles with less light than this are too dark to make out who or what is on them.
+const LIT_THRESHOLD: f32 = 0.2;
+/// Visible but unlit tiles are still drawn this bright, so the player can see the layout.
+const MIN_BRIGHTNESS: f32 = 0.3;
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum TileType {
     Floor,
     Wall,
     DownStairs,
+    WoodenDoor,
+    Barricade,
+    ShallowWater,
+    DeepWater,
+    Lava,
+    Chasm,
+}
+
+impl TileType {
+    pub fn is_walkable(&self) -> bool {
+        match self {
+            TileType::Wall | TileType::Barricade => false,
+            _ => true,
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            TileType::Wall | TileType::WoodenDoor => true,
+            _ => false,
+        }
+    }
+
+    /// Cost of stepping onto the tile when pathing, or `None` if nothing should path onto it.
+    /// Never below 1.0, so the straight-line distance used by `get_pathing_distance` stays a
+    /// safe A* estimate.
+    pub fn movement_cost(&self) -> Option<f32> {
+        match self {
+            TileType::Wall | TileType::Barricade | TileType::Chasm => None,
+            TileType::ShallowWater => Some(2.0),
+            TileType::DeepWater => Some(3.0),
+            TileType::Lava => Some(10.0),
+            _ => Some(1.0),
+        }
+    }
+
+    /// Terrain that can hurt, strand or swallow whoever walks onto it.
+    pub fn is_hazardous(&self) -> bool {
+        match self {
+            TileType::DeepWater | TileType::Lava | TileType::Chasm => true,
+            _ => false,
+        }
+    }
+
+    /// Water puts out anything that is burning.
+    pub fn is_water(&self) -> bool {
+        match self {
+            TileType::ShallowWater | TileType::DeepWater => true,
+            _ => false,
+        }
+    }
+
+    /// Weak terrain that a fireball can blow apart.
+    pub fn is_destructible(&self) -> bool {
+        match self {
+            TileType::WoodenDoor | TileType::Barricade => true,
+            _ => false,
+        }
+    }
+}
+
+/// What the player last saw on a tile, drawn greyed out while the tile is out of view.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub struct RememberedGlyph {
+    pub glyph: u8,
+    pub fg: RGB,
 }
 
 #[derive(PartialEq, Clone, Serialize, Deserialize)]
@@ -29,14 +108,20 @@ pub struct Map {
     pub visible_tiles: Vec<bool>,
     pub blocked_tiles: Vec<bool>,
     pub depth: i32,
+    pub start_idx: usize,
+    pub remembered: Vec<Option<RememberedGlyph>>,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub tile_content: Vec<Vec<Entity>>,
+
+    /// How much light falls on each tile, worked out by the lighting system every turn.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub light: Vec<RGB>,
 }
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx as usize] == TileType::Wall
+        self.tiles[idx as usize].is_opaque()
     }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
@@ -61,7 +146,7 @@ impl Algorithm2D for Map {
 
 impl Map {
     pub fn is_inside_map(&self, x: i32, y: i32) -> bool {
-        x >= 0 && x <= self.width && y >= 0 && y <= self.height
+        x >= 0 && x < self.width && y >= 0 && y < self.height
     }
 
     pub fn is_floor_available(&self, x: i32, y: i32) -> bool {
@@ -82,18 +167,24 @@ impl Map {
         Position { x, y }
     }
 
-    pub fn draw(&self, ctx: &mut Rltk) {
+    pub fn draw(&self, ctx: &mut Rltk, theme: &Theme) {
         let mut y = 0;
         let mut x = 0;
-        for (idx, tile) in self.tiles.iter().enumerate() {
+        for idx in 0..self.tiles.len() {
             // Render a tile depending upon the tile type
             if self.revealed_tiles[idx] {
-                let (glyph, mut fg) = match tile {
-                    TileType::Floor => (rltk::to_cp437('•'), RGB::from_f32(0.243, 0.537, 0.282)),
-                    TileType::Wall => (rltk::to_cp437('#'), RGB::from_f32(0.451, 0.243, 0.224)),
-                    TileType::DownStairs => (rltk::to_cp437('v'), RGB::from_f32(0., 1.0, 1.0)),
-                };
-                if !self.visible_tiles[idx] {
+                let (mut glyph, mut fg) = theme.tile_glyph(self, idx);
+                if self.visible_tiles[idx] {
+                    let light = self.light[idx];
+                    fg = RGB::from_f32(
+                        fg.r * f32::max(light.r, MIN_BRIGHTNESS),
+                        fg.g * f32::max(light.g, MIN_BRIGHTNESS),
+                        fg.b * f32::max(light.b, MIN_BRIGHTNESS),
+                    );
+                } else if let Some(memory) = &self.remembered[idx] {
+                    glyph = memory.glyph;
+                    fg = memory.fg.to_greyscale();
+                } else {
                     fg = fg.to_greyscale()
                 }
                 ctx.set(x, y, fg, RGB::from_f32(0., 0., 0.), glyph);
@@ -108,8 +199,9 @@ impl Map {
         }
     }
 
-    pub fn new_rooms_and_corridors(rng: &mut RandomNumberGenerator, depth: i32) -> Map {
-        let mut map = Map {
+    /// Creates a map filled with walls, ready to be carved by a builder.
+    pub fn new(depth: i32) -> Map {
+        Map {
             tiles: vec![TileType::Wall; MAP_SIZE],
             rooms: Vec::new(),
             width: MAP_WIDTH,
@@ -118,8 +210,19 @@ impl Map {
             visible_tiles: vec![false; MAP_SIZE],
             blocked_tiles: vec![false; MAP_SIZE],
             tile_content: vec![Vec::new(); MAP_SIZE],
+            light: vec![FULL_LIGHT; MAP_SIZE],
             depth,
-        };
+            start_idx: 0,
+            remembered: vec![None; MAP_SIZE],
+        }
+    }
+
+    pub fn start_position(&self) -> Position {
+        self.pos_from_idx(self.start_idx)
+    }
+
+    pub fn new_rooms_and_corridors(rng: &mut RandomNumberGenerator, depth: i32) -> Map {
+        let mut map = Map::new(depth);
 
         while map.rooms.len() < MAX_ROOMS {
             let width = rng.range(MIN_ROOM_SIZE, MAX_ROOM_SIZE);
@@ -151,19 +254,66 @@ impl Map {
             }
         }
 
-        let stairs_position = map.rooms[MAX_ROOMS-1].center();
-        let stairs_idx = map.idx_from_pos(stairs_position);
+        for room in map.rooms.clone().iter() {
+            map.add_doors(rng, room);
+        }
+
+        // The stairs go in the room that takes longest to walk to
+        map.start_idx = map.idx_from_pos(map.rooms[0].center());
+        let distances = DijkstraMap::new(&map, &[map.start_idx]);
+        let stairs_idx = map
+            .rooms
+            .iter()
+            .map(|room| map.idx_from_pos(room.center()))
+            .filter(|idx| distances.is_reachable(*idx))
+            .max_by(|a, b| {
+                distances.distances[*a]
+                    .partial_cmp(&distances.distances[*b])
+                    .unwrap()
+            })
+            .unwrap();
         map.tiles[stairs_idx] = TileType::DownStairs;
 
         map
     }
 
+    /// Whether a tile has enough light on it to make out what's there.
+    pub fn is_lit(&self, idx: usize) -> bool {
+        let light = self.light[idx];
+        f32::max(light.r, f32::max(light.g, light.b)) >= LIT_THRESHOLD
+    }
+
+    /// Whether the player, standing at `viewer`, can make out an entity on a tile: it has to be
+    /// in view, and either lit or right next to them.
+    pub fn can_make_out(&self, idx: usize, viewer: Point) -> bool {
+        let Position { x, y } = self.pos_from_idx(idx);
+        let adjacent = (x - viewer.x).abs() <= 1 && (y - viewer.y).abs() <= 1;
+        self.visible_tiles[idx] && (adjacent || self.is_lit(idx))
+    }
+
     pub fn update_blocked_tiles(&mut self) {
         for (i, tile) in self.tiles.iter_mut().enumerate() {
-            self.blocked_tiles[i] = *tile == TileType::Wall;
+            self.blocked_tiles[i] = !tile.is_walkable();
         }
     }
 
+    /// Walls can be dug out, except for the outer edge of the map.
+    pub fn is_diggable(&self, idx: usize) -> bool {
+        let Position { x, y } = self.pos_from_idx(idx);
+        self.tiles[idx] == TileType::Wall
+            && x > 0
+            && x < self.width - 1
+            && y > 0
+            && y < self.height - 1
+    }
+
+    /// Changes a tile during play, keeping `blocked_tiles` in step. Anything that could see the
+    /// tile needs its viewshed marked dirty afterwards.
+    pub fn change_tile(&mut self, idx: usize, tile: TileType) {
+        self.blocked_tiles[idx] = !tile.is_walkable();
+        self.tiles[idx] = tile;
+    }
+
     pub fn clear_content_index(&mut self) {
         for content in &mut self.tile_content {
             content.clear();
@@ -179,6 +329,60 @@ impl Map {
         }
     }
 
+    /// Marks every viewshed that currently sees one of the changed tiles as dirty, so it gets
+    /// recomputed with the new terrain.
+    pub fn dirty_viewsheds_seeing(
+        &self,
+        viewsheds: &mut WriteStorage<Viewshed>,
+        changed: &[usize],
+    ) {
+        for viewshed in viewsheds.join() {
+            let sees_change = viewshed
+                .visible_tiles
+                .iter()
+                .any(|point| changed.contains(&self.xy_idx(point.x, point.y)));
+            if sees_change {
+                viewshed.dirty = true;
+            }
+        }
+    }
+
+    /// Puts wooden doors, and the odd barricade, where corridors enter a room.
+    fn add_doors(&mut self, rng: &mut RandomNumberGenerator, room: &Rect) {
+        let mut doorways = Vec::new();
+        for x in room.x1 + 1..=room.x2 {
+            doorways.push((x, room.y1, true));
+            doorways.push((x, room.y2 + 1, true));
+        }
+        for y in room.y1 + 1..=room.y2 {
+            doorways.push((room.x1, y, false));
+            doorways.push((room.x2 + 1, y, false));
+        }
+
+        for (x, y, horizontal_wall) in doorways {
+            if !self.is_inside_map(x, y) || self.tiles[self.xy_idx(x, y)] != TileType::Floor {
+                continue;
+            }
+            let framed = if horizontal_wall {
+                self.is_wall(x - 1, y) && self.is_wall(x + 1, y)
+            } else {
+                self.is_wall(x, y - 1) && self.is_wall(x, y + 1)
+            };
+            if framed {
+                let idx = self.xy_idx(x, y);
+                self.tiles[idx] = match rng.roll_dice(1, 6) {
+                    1 => TileType::Barricade,
+                    2 | 3 | 4 => TileType::WoodenDoor,
+                    _ => TileType::Floor,
+                };
+            }
+        }
+    }
+
+    fn is_wall(&self, x: i32, y: i32) -> bool {
+        self.tiles[self.xy_idx(x, y)] == TileType::Wall
+    }
+
     fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
         for x in min(x1, x2)..=max(x1, x2) {
             let idx = self.xy_idx(x, y);
@@ -202,65 +406,28 @@ impl Map {
         let Position { x, y } = self.pos_from_idx(idx);
 
         // Cardinal directions
-        if self.is_floor_available(x - 1, y) {
-            floors.push((self.west_idx(idx), 1.0))
-        };
-        if self.is_floor_available(x + 1, y) {
-            floors.push((self.east_idx(idx), 1.0))
-        };
-        if self.is_floor_available(x, y - 1) {
-            floors.push((self.north_idx(idx), 1.0))
-        };
-        if self.is_floor_available(x, y + 1) {
-            floors.push((self.south_idx(idx), 1.0))
-        };
+        self.push_floor(&mut floors, x - 1, y, 1.0);
+        self.push_floor(&mut floors, x + 1, y, 1.0);
+        self.push_floor(&mut floors, x, y - 1, 1.0);
+        self.push_floor(&mut floors, x, y + 1, 1.0);
 
         // Diagonals
-        if self.is_floor_available(x - 1, y - 1) {
-            floors.push((self.north_west_idx(idx), 1.45));
-        }
-        if self.is_floor_available(x + 1, y - 1) {
-            floors.push((self.north_east_idx(idx), 1.45));
-        }
-        if self.is_floor_available(x - 1, y + 1) {
-            floors.push((self.south_west_idx(idx), 1.45));
-        }
-        if self.is_floor_available(x + 1, y + 1) {
-            floors.push((self.south_east_idx(idx), 1.45));
-        }
+        self.push_floor(&mut floors, x - 1, y - 1, 1.45);
+        self.push_floor(&mut floors, x + 1, y - 1, 1.45);
+        self.push_floor(&mut floors, x - 1, y + 1, 1.45);
+        self.push_floor(&mut floors, x + 1, y + 1, 1.45);
 
         floors
     }
 
-    fn north_idx(&self, idx: usize) -> usize {
-        idx - self.width as usize
-    }
-
-    fn east_idx(&self, idx: usize) -> usize {
-        idx + 1
-    }
-
-    fn south_idx(&self, idx: usize) -> usize {
-        idx + self.width as usize
-    }
-
-    fn west_idx(&self, idx: usize) -> usize {
-        idx - 1
-    }
-
-    fn south_east_idx(&self, idx: usize) -> usize {
-        self.east_idx(self.south_idx(idx))
-    }
-
-    fn south_west_idx(&self, idx: usize) -> usize {
-        self.west_idx(self.south_idx(idx))
-    }
-
-    fn north_east_idx(&self, idx: usize) -> usize {
-        self.east_idx(self.north_idx(idx))
-    }
-
-    fn north_west_idx(&self, idx: usize) -> usize {
-        self.west_idx(self.north_idx(idx))
+    /// Adds a neighbouring tile as an exit, weighting the step by the terrain being entered.
+    fn push_floor(&self, floors: &mut Vec<(usize, f32)>, x: i32, y: i32, distance: f32) {
+        if !self.is_floor_available(x, y) {
+            return;
+        }
+        let idx = self.xy_idx(x, y);
+        if let Some(cost) = self.tiles[idx].movement_cost() {
+            floors.push((idx, distance * cost));
+        }
     }
 }
@@ -0,0 +1,58 @@
+use crate::{Map, Rect, TileType};
+use rltk::RandomNumberGenerator;
+
+const HALL_MARGIN: i32 = 3;
+const HALL_LEFT: i32 = 20;
+const PILLAR_SPACING: i32 = 8;
+
+/// Builds the boss's lair at the bottom of the dungeon: a small antechamber on the west side,
+/// where the player arrives, opening onto a great pillared hall. There's no way further down.
+/// The hall is the last of the map's rooms; the boss waits in its middle.
+pub fn build(rng: &mut RandomNumberGenerator, depth: i32) -> Map {
+    let mut map = Map::new(depth);
+
+    let antechamber = Rect::new(2, map.height / 2 - 3, 8, 6);
+    let hall = Rect::new(
+        HALL_LEFT,
+        HALL_MARGIN,
+        map.width - HALL_LEFT - HALL_MARGIN - 1,
+        map.height - HALL_MARGIN * 2 - 1,
+    );
+    carve(&mut map, &antechamber);
+    carve(&mut map, &hall);
+
+    let corridor_y = antechamber.center().y;
+    for x in antechamber.x2..=hall.x1 {
+        let idx = map.xy_idx(x, corridor_y);
+        map.tiles[idx] = TileType::Floor;
+    }
+
+    // Two rows of pillars to duck behind, a few tiles in from the hall's long walls. Now and then
+    // one has crumbled away.
+    for x in (hall.x1 + PILLAR_SPACING / 2..hall.x2 - 2).step_by(PILLAR_SPACING as usize) {
+        for y in [hall.y1 + 4, hall.y2 - 4].iter() {
+            if rng.roll_dice(1, 6) == 1 {
+                continue;
+            }
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                let idx = map.xy_idx(x + dx, y + dy);
+                map.tiles[idx] = TileType::Wall;
+            }
+        }
+    }
+
+    map.start_idx = map.idx_from_pos(antechamber.center());
+    map.rooms.push(antechamber);
+    map.rooms.push(hall);
+
+    map
+}
+
+fn carve(map: &mut Map, room: &Rect) {
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use super::common::{
+    closest_floor_to_centre, keep_largest_region, place_stairs_farthest_from_start,
+};
+use crate::{Map, TileType};
+use rltk::RandomNumberGenerator;
+
+const FLOOR_CHANCE: i32 = 55;
+const SMOOTHING_ITERATIONS: i32 = 15;
+
+/// Builds a cave level by smoothing random noise with a cellular automaton.
+pub fn build(rng: &mut RandomNumberGenerator, depth: i32) -> Map {
+    let mut map = Map::new(depth);
+
+    // Start from noise, keeping the outer edge solid
+    for y in 1..map.height - 1 {
+        for x in 1..map.width - 1 {
+            if rng.roll_dice(1, 100) <= FLOOR_CHANCE {
+                let idx = map.xy_idx(x, y);
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+    }
+
+    for _i in 0..SMOOTHING_ITERATIONS {
+        smooth(&mut map);
+    }
+
+    keep_largest_region(&mut map);
+    map.start_idx = closest_floor_to_centre(&map);
+    place_stairs_farthest_from_start(&mut map);
+
+    map
+}
+
+fn smooth(map: &mut Map) {
+    let mut new_tiles = map.tiles.clone();
+    for y in 1..map.height - 1 {
+        for x in 1..map.width - 1 {
+            let walls = count_neighbouring_walls(map, x, y);
+            let idx = map.xy_idx(x, y);
+            new_tiles[idx] = if walls > 4 || walls == 0 {
+                TileType::Wall
+            } else {
+                TileType::Floor
+            };
+        }
+    }
+    map.tiles = new_tiles;
+}
+
+fn count_neighbouring_walls(map: &Map, x: i32, y: i32) -> i32 {
+    let mut walls = 0;
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if (dx != 0 || dy != 0) && map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall {
+                walls += 1;
+            }
+        }
+    }
+    walls
+}
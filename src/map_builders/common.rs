@@ -0,0 +1,111 @@
+use crate::dijkstra::DijkstraMap;
+use crate::{Map, TileType};
+use rltk::{Point, RandomNumberGenerator};
+
+/// Walls off every open area except the largest connected one.
+pub fn keep_largest_region(map: &mut Map) {
+    let mut regions = connected_regions(map);
+    regions.sort_by_key(|region| region.len());
+    regions.pop();
+    for region in regions.iter() {
+        for idx in region.iter() {
+            map.tiles[*idx] = TileType::Wall;
+        }
+    }
+}
+
+fn connected_regions(map: &Map) -> Vec<Vec<usize>> {
+    let mut regions = Vec::new();
+    let mut assigned = vec![false; map.tiles.len()];
+    for idx in 0..map.tiles.len() {
+        if assigned[idx] || map.tiles[idx] == TileType::Wall {
+            continue;
+        }
+
+        let distances = DijkstraMap::new(map, &[idx]);
+        let region: Vec<usize> = (0..map.tiles.len())
+            .filter(|tile| distances.is_reachable(*tile))
+            .collect();
+        for tile in region.iter() {
+            assigned[*tile] = true;
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+/// Finds the open tile nearest to the middle of the map, a good place to start the player.
+pub fn closest_floor_to_centre(map: &Map) -> usize {
+    let centre = Point::new(map.width / 2, map.height / 2);
+    map.tiles
+        .iter()
+        .enumerate()
+        .filter(|(_idx, tile)| **tile == TileType::Floor)
+        .map(|(idx, _tile)| {
+            let pos = map.pos_from_idx(idx);
+            let distance =
+                rltk::DistanceAlg::PythagorasSquared.distance2d(centre, Point::new(pos.x, pos.y));
+            (idx, distance)
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(idx, _distance)| idx)
+        .expect("Map has no floor")
+}
+
+/// Puts the down stairs on the reachable tile that is farthest from `map.start_idx`.
+pub fn place_stairs_farthest_from_start(map: &mut Map) {
+    let farthest = DijkstraMap::new(map, &[map.start_idx])
+        .farthest()
+        .expect("Map has no reachable tiles");
+    map.tiles[farthest] = TileType::DownStairs;
+}
+
+/// Splits the open tiles of a map into Voronoi cells around randomly chosen seeds. Used to
+/// spawn things on levels that have no rooms.
+pub fn voronoi_regions(
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+    seed_count: usize,
+) -> Vec<Vec<usize>> {
+    let open_tiles: Vec<usize> = map
+        .tiles
+        .iter()
+        .enumerate()
+        .filter(|(_idx, tile)| **tile != TileType::Wall)
+        .map(|(idx, _tile)| idx)
+        .collect();
+    if open_tiles.is_empty() {
+        return Vec::new();
+    }
+
+    let mut seeds: Vec<Point> = Vec::new();
+    while seeds.len() < usize::min(seed_count, open_tiles.len()) {
+        let idx = open_tiles[rng.range(0, open_tiles.len() as i32) as usize];
+        let pos = map.pos_from_idx(idx);
+        let seed = Point::new(pos.x, pos.y);
+        if !seeds.contains(&seed) {
+            seeds.push(seed);
+        }
+    }
+
+    let mut regions: Vec<Vec<usize>> = vec![Vec::new(); seeds.len()];
+    for idx in open_tiles {
+        let pos = map.pos_from_idx(idx);
+        let point = Point::new(pos.x, pos.y);
+        let closest = seeds
+            .iter()
+            .enumerate()
+            .map(|(i, seed)| {
+                (
+                    i,
+                    rltk::DistanceAlg::PythagorasSquared.distance2d(*seed, point),
+                )
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(i, _distance)| i)
+            .unwrap();
+        regions[closest].push(idx);
+    }
+
+    regions
+}
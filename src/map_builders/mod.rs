@@ -0,0 +1,32 @@
+use crate::Map;
+use rltk::RandomNumberGenerator;
+
+mod arena;
+mod cellular_automata;
+mod common;
+mod terrain_features;
+mod town;
+mod waveform_collapse;
+pub use common::*;
+
+/// Depth of the surface town where every run starts.
+pub const TOWN_DEPTH: i32 = 0;
+
+/// Picks a generator for the given depth and builds a new level with it. The dungeon ends at
+/// `final_depth`, in the boss's lair.
+pub fn build_level(rng: &mut RandomNumberGenerator, depth: i32, final_depth: i32) -> Map {
+    if depth == TOWN_DEPTH {
+        return town::build(rng, depth);
+    }
+    if depth == final_depth {
+        return arena::build(rng, depth);
+    }
+
+    let mut map = match rng.roll_dice(1, 3) {
+        1 => Map::new_rooms_and_corridors(rng, depth),
+        2 => cellular_automata::build(rng, depth),
+        _ => waveform_collapse::build(rng, depth),
+    };
+    terrain_features::add_terrain_features(rng, &mut map);
+    map
+}
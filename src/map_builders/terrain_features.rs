@@ -0,0 +1,105 @@
+use crate::{Map, TileType};
+use rltk::{Point, RandomNumberGenerator};
+use std::collections::VecDeque;
+
+const MAX_FEATURES: i32 = 4;
+const LAVA_MIN_DEPTH: i32 = 4;
+
+/// Scatters pools of water, lava and chasms over the open floor of a level. A feature that would
+/// leave the stairs out of reach without crossing something hazardous is thrown away again.
+pub fn add_terrain_features(rng: &mut RandomNumberGenerator, map: &mut Map) {
+    let features = rng.roll_dice(1, MAX_FEATURES) - 1;
+    for _i in 0..features {
+        let before = map.tiles.clone();
+        let centre = random_floor(rng, map);
+        match rng.roll_dice(1, 4) {
+            1 | 2 => {
+                let radius = rng.range(2, 5);
+                fill_circle(map, centre, radius, TileType::ShallowWater);
+                fill_circle(map, centre, radius - 1, TileType::DeepWater);
+            }
+            3 if map.depth >= LAVA_MIN_DEPTH => {
+                fill_circle(map, centre, rng.range(1, 4), TileType::Lava);
+            }
+            _ => fill_circle(map, centre, rng.range(1, 3), TileType::Chasm),
+        }
+
+        if !stairs_safely_reachable(map) {
+            map.tiles = before;
+        }
+    }
+}
+
+fn random_floor(rng: &mut RandomNumberGenerator, map: &Map) -> Point {
+    let floors: Vec<usize> = map
+        .tiles
+        .iter()
+        .enumerate()
+        .filter(|(_idx, tile)| **tile == TileType::Floor)
+        .map(|(idx, _tile)| idx)
+        .collect();
+    let pos = map.pos_from_idx(floors[rng.range(0, floors.len() as i32) as usize]);
+    Point::new(pos.x, pos.y)
+}
+
+/// Turns the plain floor within `radius` of `centre` into `tile`, leaving walls, doors, stairs
+/// and the player's start alone.
+fn fill_circle(map: &mut Map, centre: Point, radius: i32, tile: TileType) {
+    for y in centre.y - radius..=centre.y + radius {
+        for x in centre.x - radius..=centre.x + radius {
+            if x < 0 || x >= map.width || y < 0 || y >= map.height {
+                continue;
+            }
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(centre, Point::new(x, y));
+            let idx = map.xy_idx(x, y);
+            if distance <= radius as f32 + 0.5
+                && idx != map.start_idx
+                && map.tiles[idx] != TileType::Wall
+                && map.tiles[idx] != TileType::DownStairs
+                && map.tiles[idx] != TileType::WoodenDoor
+                && map.tiles[idx] != TileType::Barricade
+            {
+                map.tiles[idx] = tile.clone();
+            }
+        }
+    }
+}
+
+/// Checks that the down stairs can be reached from the start on foot, without swimming,
+/// burning or falling.
+fn stairs_safely_reachable(map: &Map) -> bool {
+    let stairs = match map
+        .tiles
+        .iter()
+        .position(|tile| *tile == TileType::DownStairs)
+    {
+        Some(stairs) => stairs,
+        None => return true,
+    };
+
+    let mut reached = vec![false; map.tiles.len()];
+    let mut open_list = VecDeque::new();
+    reached[map.start_idx] = true;
+    open_list.push_back(map.start_idx);
+    while let Some(idx) = open_list.pop_front() {
+        if idx == stairs {
+            return true;
+        }
+        let pos = map.pos_from_idx(idx);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (x, y) = (pos.x + dx, pos.y + dy);
+                if x < 0 || x >= map.width || y < 0 || y >= map.height {
+                    continue;
+                }
+                let neighbour = map.xy_idx(x, y);
+                let tile = &map.tiles[neighbour];
+                if !reached[neighbour] && tile.is_walkable() && !tile.is_hazardous() {
+                    reached[neighbour] = true;
+                    open_list.push_back(neighbour);
+                }
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,65 @@
+use crate::{Map, Rect, TileType};
+use rltk::RandomNumberGenerator;
+
+const BUILDINGS: usize = 6;
+const MIN_BUILDING_WIDTH: i32 = 6;
+const MAX_BUILDING_WIDTH: i32 = 11;
+const MIN_BUILDING_HEIGHT: i32 = 4;
+const MAX_BUILDING_HEIGHT: i32 = 7;
+const EDGE_MARGIN: i32 = 8;
+
+/// Builds the surface town: an open field with a handful of buildings, the player starting on
+/// the west side and the way down on the east side. The first building belongs to the healer.
+pub fn build(rng: &mut RandomNumberGenerator, depth: i32) -> Map {
+    let mut map = Map::new(depth);
+
+    for y in 1..map.height - 1 {
+        for x in 1..map.width - 1 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+
+    let mut attempts = 0;
+    while map.rooms.len() < BUILDINGS && attempts < 1000 {
+        attempts += 1;
+        let width = rng.range(MIN_BUILDING_WIDTH, MAX_BUILDING_WIDTH);
+        let height = rng.range(MIN_BUILDING_HEIGHT, MAX_BUILDING_HEIGHT);
+        let x = rng.range(EDGE_MARGIN, map.width - EDGE_MARGIN - width);
+        let y = rng.range(3, map.height - 4 - height);
+        let building = Rect::new(x, y, width, height);
+
+        // Leave a street around every building so the town stays connected
+        let with_street = Rect::new(x - 2, y - 2, width + 4, height + 4);
+        if map.rooms.iter().any(|other| with_street.intersect(other)) {
+            continue;
+        }
+
+        apply_building(&mut map, &building);
+        map.rooms.push(building);
+    }
+
+    map.start_idx = map.xy_idx(2, map.height / 2);
+    let stairs_idx = map.xy_idx(map.width - 3, map.height / 2);
+    map.tiles[stairs_idx] = TileType::DownStairs;
+
+    map
+}
+
+fn apply_building(map: &mut Map, building: &Rect) {
+    for y in building.y1..=building.y2 {
+        for x in building.x1..=building.x2 {
+            let is_edge =
+                x == building.x1 || x == building.x2 || y == building.y1 || y == building.y2;
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = if is_edge {
+                TileType::Wall
+            } else {
+                TileType::Floor
+            };
+        }
+    }
+
+    let door_idx = map.xy_idx((building.x1 + building.x2) / 2, building.y2);
+    map.tiles[door_idx] = TileType::Floor;
+}
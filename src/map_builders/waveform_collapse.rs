@@ -0,0 +1,233 @@
+use super::cellular_automata;
+use super::common::{
+    closest_floor_to_centre, keep_largest_region, place_stairs_farthest_from_start,
+};
+use crate::{ascii_map, Map, TileType};
+use rltk::RandomNumberGenerator;
+
+const CHUNK_SIZE: i32 = 3;
+const MAX_ATTEMPTS: i32 = 10;
+const MIN_FLOOR_TILES: usize = 400;
+const HAND_AUTHORED_SAMPLE: &str = include_str!("../../maps/wfc_sample.txt");
+
+/// Offsets of the north, east, south and west neighbours of a chunk.
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// Builds a level with wave function collapse, learning which chunks of tiles may sit next to
+/// each other from a sample map: a fresh dungeon, a cave or the hand-authored sample.
+pub fn build(rng: &mut RandomNumberGenerator, depth: i32) -> Map {
+    let sample = match rng.roll_dice(1, 3) {
+        1 => Map::new_rooms_and_corridors(rng, depth),
+        2 => cellular_automata::build(rng, depth),
+        _ => ascii_map::map_from_ascii(HAND_AUTHORED_SAMPLE, depth)
+            .expect("Invalid wave function collapse sample"),
+    };
+    build_from_sample(rng, &sample, depth)
+}
+
+pub fn build_from_sample(rng: &mut RandomNumberGenerator, sample: &Map, depth: i32) -> Map {
+    let patterns = Patterns::learn(sample);
+    for _attempt in 0..MAX_ATTEMPTS {
+        if let Some(mut map) = patterns.solve(rng, depth) {
+            if make_playable(&mut map) {
+                return map;
+            }
+        }
+    }
+
+    // The sample doesn't tile well; settle for a regular dungeon rather than looping forever
+    Map::new_rooms_and_corridors(rng, depth)
+}
+
+/// Seals the edges, keeps the largest open area and places the start and the stairs. Returns
+/// false if what's left is too small to be worth playing.
+fn make_playable(map: &mut Map) -> bool {
+    for x in 0..map.width {
+        let top = map.xy_idx(x, 0);
+        let bottom = map.xy_idx(x, map.height - 1);
+        map.tiles[top] = TileType::Wall;
+        map.tiles[bottom] = TileType::Wall;
+    }
+    for y in 0..map.height {
+        let left = map.xy_idx(0, y);
+        let right = map.xy_idx(map.width - 1, y);
+        map.tiles[left] = TileType::Wall;
+        map.tiles[right] = TileType::Wall;
+    }
+
+    keep_largest_region(map);
+    let floors = map
+        .tiles
+        .iter()
+        .filter(|tile| **tile == TileType::Floor)
+        .count();
+    if floors < MIN_FLOOR_TILES {
+        return false;
+    }
+
+    map.start_idx = closest_floor_to_centre(map);
+    place_stairs_farthest_from_start(map);
+    true
+}
+
+struct Patterns {
+    chunks: Vec<Vec<TileType>>,
+    /// For every chunk and direction, the chunks that may be placed on that side of it.
+    compatible: Vec<[Vec<usize>; 4]>,
+}
+
+impl Patterns {
+    /// Collects every distinct chunk of the sample, treating stairs as floor.
+    fn learn(sample: &Map) -> Patterns {
+        let mut chunks: Vec<Vec<TileType>> = Vec::new();
+        for y in 0..=sample.height - CHUNK_SIZE {
+            for x in 0..=sample.width - CHUNK_SIZE {
+                let mut chunk = Vec::new();
+                for cy in 0..CHUNK_SIZE {
+                    for cx in 0..CHUNK_SIZE {
+                        let tile = match sample.tiles[sample.xy_idx(x + cx, y + cy)] {
+                            TileType::DownStairs => TileType::Floor,
+                            ref tile => tile.clone(),
+                        };
+                        chunk.push(tile);
+                    }
+                }
+                if !chunks.contains(&chunk) {
+                    chunks.push(chunk);
+                }
+            }
+        }
+
+        let mut compatible = Vec::new();
+        for chunk in chunks.iter() {
+            let mut sides: [Vec<usize>; 4] = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+            for (direction, side) in sides.iter_mut().enumerate() {
+                for (other_idx, other) in chunks.iter().enumerate() {
+                    if edges_match(chunk, other, direction) {
+                        side.push(other_idx);
+                    }
+                }
+            }
+            compatible.push(sides);
+        }
+
+        Patterns { chunks, compatible }
+    }
+
+    /// Runs the solver once. Returns `None` if it reached a contradiction.
+    fn solve(&self, rng: &mut RandomNumberGenerator, depth: i32) -> Option<Map> {
+        let mut map = Map::new(depth);
+        let chunks_x = (map.width / CHUNK_SIZE) as usize;
+        let chunks_y = (map.height / CHUNK_SIZE) as usize;
+        let mut cells = vec![vec![true; self.chunks.len()]; chunks_x * chunks_y];
+
+        // Collapse the most constrained cell until every cell has a single option left
+        loop {
+            let option_counts: Vec<usize> = cells
+                .iter()
+                .map(|options| options.iter().filter(|possible| **possible).count())
+                .collect();
+            let fewest = match option_counts.iter().filter(|count| **count > 1).min() {
+                None => break,
+                Some(fewest) => *fewest,
+            };
+            let candidates: Vec<usize> = option_counts
+                .iter()
+                .enumerate()
+                .filter(|(_cell, count)| **count == fewest)
+                .map(|(cell, _count)| cell)
+                .collect();
+            let cell = candidates[rng.range(0, candidates.len() as i32) as usize];
+
+            let options: Vec<usize> = cells[cell]
+                .iter()
+                .enumerate()
+                .filter(|(_chunk, possible)| **possible)
+                .map(|(chunk, _possible)| chunk)
+                .collect();
+            let choice = options[rng.range(0, options.len() as i32) as usize];
+            for (chunk, possible) in cells[cell].iter_mut().enumerate() {
+                *possible = chunk == choice;
+            }
+
+            if !self.propagate(&mut cells, cell, chunks_x, chunks_y) {
+                return None;
+            }
+        }
+
+        for (cell, options) in cells.iter().enumerate() {
+            let chunk = &self.chunks[options.iter().position(|possible| *possible)?];
+            let left = (cell % chunks_x) as i32 * CHUNK_SIZE;
+            let top = (cell / chunks_x) as i32 * CHUNK_SIZE;
+            for (i, tile) in chunk.iter().enumerate() {
+                let idx = map.xy_idx(left + i as i32 % CHUNK_SIZE, top + i as i32 / CHUNK_SIZE);
+                map.tiles[idx] = tile.clone();
+            }
+        }
+
+        Some(map)
+    }
+
+    /// Removes options that no longer fit next to their neighbours, spreading out from `start`.
+    /// Returns false if a cell runs out of options.
+    fn propagate(
+        &self,
+        cells: &mut Vec<Vec<bool>>,
+        start: usize,
+        chunks_x: usize,
+        chunks_y: usize,
+    ) -> bool {
+        let mut open_list = vec![start];
+        while let Some(cell) = open_list.pop() {
+            let (x, y) = ((cell % chunks_x) as i32, (cell / chunks_x) as i32);
+            for (direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || nx >= chunks_x as i32 || ny < 0 || ny >= chunks_y as i32 {
+                    continue;
+                }
+                let neighbour = ny as usize * chunks_x + nx as usize;
+
+                let mut allowed = vec![false; self.chunks.len()];
+                for (chunk, possible) in cells[cell].iter().enumerate() {
+                    if *possible {
+                        for other in self.compatible[chunk][direction].iter() {
+                            allowed[*other] = true;
+                        }
+                    }
+                }
+
+                let mut changed = false;
+                for (possible, allowed) in cells[neighbour].iter_mut().zip(allowed.iter()) {
+                    if *possible && !*allowed {
+                        *possible = false;
+                        changed = true;
+                    }
+                }
+
+                if changed {
+                    if !cells[neighbour].iter().any(|possible| *possible) {
+                        return false;
+                    }
+                    open_list.push(neighbour);
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Checks whether `other` may sit on the given side of `chunk`: the rows or columns where the
+/// two touch must be identical.
+fn edges_match(chunk: &[TileType], other: &[TileType], direction: usize) -> bool {
+    let last = CHUNK_SIZE - 1;
+    (0..CHUNK_SIZE).all(|i| {
+        let (mine, theirs) = match direction {
+            0 => ((i, 0), (i, last)),
+            1 => ((last, i), (0, i)),
+            2 => ((i, last), (i, 0)),
+            _ => ((0, i), (last, i)),
+        };
+        chunk[(mine.1 * CHUNK_SIZE + mine.0) as usize]
+            == other[(theirs.1 * CHUNK_SIZE + theirs.0) as usize]
+    })
+}
@@ -0,0 +1,73 @@
+use crate::campaign::Campaign;
+use crate::components::*;
+use crate::map_themes::Themes;
+use crate::{map_builders, spawner, Map};
+use image::{Rgb, RgbImage};
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
+use specs::saveload::SimpleMarkerAllocator;
+use std::io;
+
+/// Size in pixels of the square drawn for every tile.
+const CELL_SIZE: u32 = 8;
+
+/// Generates the level for a seed and depth, populates it and writes a picture of the whole
+/// thing to `path`. Needs no window, so it can run on a headless machine. Only the town matches
+/// what a game started with the same seed plays on: the game builds every deeper level from a
+/// fresh, unseeded generator as the player reaches it.
+pub fn render_level(seed: u64, depth: i32, path: &str) -> io::Result<()> {
+    let mut world = World::new();
+    crate::register_components(&mut world);
+
+    let mut rng = RandomNumberGenerator::seeded(seed);
+    let campaign = Campaign::load();
+    let map = map_builders::build_level(&mut rng, depth, campaign.final_depth);
+    world.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    world.insert(rng);
+    world.insert(campaign);
+
+    spawner::spawn_map(&mut world, &map);
+    spawner::player(&mut world, map.start_position());
+    world.insert(map);
+    world.insert(Themes::load());
+
+    render_world(&world).save(path)
+}
+
+/// Draws every tile of the current map, revealed or not, in its theme colour and overlays each
+/// entity as a smaller square in its foreground colour.
+pub fn render_world(world: &World) -> RgbImage {
+    let map = world.fetch::<Map>();
+    let positions = world.read_storage::<Position>();
+    let renderables = world.read_storage::<Renderable>();
+    let themes = world.fetch::<Themes>();
+    let theme = themes.for_depth(map.depth);
+
+    let mut image = RgbImage::new(map.width as u32 * CELL_SIZE, map.height as u32 * CELL_SIZE);
+    for idx in 0..map.tiles.len() {
+        let pos = map.pos_from_idx(idx);
+        let (_glyph, fg) = theme.tile_glyph(&map, idx);
+        fill_cell(&mut image, pos.x as u32, pos.y as u32, 0, fg);
+    }
+
+    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
+    data.sort_by(|&a, &b| a.1.index.cmp(&b.1.index));
+    for (pos, render) in data.iter() {
+        fill_cell(&mut image, pos.x as u32, pos.y as u32, 1, render.fg);
+    }
+
+    image
+}
+
+fn fill_cell(image: &mut RgbImage, x: u32, y: u32, inset: u32, colour: RGB) {
+    let pixel = Rgb([to_byte(colour.r), to_byte(colour.g), to_byte(colour.b)]);
+    for py in y * CELL_SIZE + inset..(y + 1) * CELL_SIZE - inset {
+        for px in x * CELL_SIZE + inset..(x + 1) * CELL_SIZE - inset {
+            image.put_pixel(px, py, pixel);
+        }
+    }
+}
+
+fn to_byte(channel: f32) -> u8 {
+    (channel * 255.0).round() as u8
+}
@@ -0,0 +1,221 @@
+use crate::map::{Map, TileType};
+use rltk::RGB;
+use serde::Deserialize;
+
+const THEME_DATA: &str = include_str!("../data/themes.json");
+const PALETTE: &str = include_str!("../palette.css");
+
+/// How one kind of tile looks under a theme.
+pub struct TileStyle {
+    pub glyph: u8,
+    pub fg: RGB,
+}
+
+/// The look of the levels within a range of depths.
+pub struct Theme {
+    pub name: String,
+    pub min_depth: i32,
+    pub max_depth: Option<i32>,
+    pub floor: TileStyle,
+    pub wall: TileStyle,
+    pub down_stairs: TileStyle,
+    pub door: TileStyle,
+    pub barricade: TileStyle,
+    pub shallow_water: TileStyle,
+    pub deep_water: TileStyle,
+    pub lava: TileStyle,
+    pub chasm: TileStyle,
+    /// How brightly lit the levels are before any light sources, from 0.0 (pitch black) to 1.0.
+    pub ambient_light: f32,
+    /// Draw walls with box-drawing glyphs that join up with the walls next to them.
+    pub connected_walls: bool,
+}
+
+/// Every theme in `data/themes.json`, available to the game as a resource.
+pub struct Themes {
+    themes: Vec<Theme>,
+}
+
+#[derive(Deserialize)]
+struct TileStyleData {
+    glyph: char,
+    colour: String,
+}
+
+#[derive(Deserialize)]
+struct ThemeData {
+    name: String,
+    min_depth: i32,
+    max_depth: Option<i32>,
+    floor: TileStyleData,
+    wall: TileStyleData,
+    down_stairs: TileStyleData,
+    door: TileStyleData,
+    barricade: TileStyleData,
+    shallow_water: TileStyleData,
+    deep_water: TileStyleData,
+    lava: TileStyleData,
+    chasm: TileStyleData,
+    ambient_light: f32,
+    connected_walls: bool,
+}
+
+impl Themes {
+    /// Parses the theme data. Colours are written as hex codes and must be ones listed in
+    /// `palette.css`, so every theme sticks to the game's palette.
+    pub fn load() -> Themes {
+        let data: Vec<ThemeData> =
+            serde_json::from_str(THEME_DATA).expect("Unable to parse themes.json");
+        let palette = parse_palette(PALETTE);
+
+        let themes = data
+            .into_iter()
+            .map(|theme| Theme {
+                floor: tile_style(&theme.floor, &palette, &theme.name),
+                wall: tile_style(&theme.wall, &palette, &theme.name),
+                down_stairs: tile_style(&theme.down_stairs, &palette, &theme.name),
+                door: tile_style(&theme.door, &palette, &theme.name),
+                barricade: tile_style(&theme.barricade, &palette, &theme.name),
+                shallow_water: tile_style(&theme.shallow_water, &palette, &theme.name),
+                deep_water: tile_style(&theme.deep_water, &palette, &theme.name),
+                lava: tile_style(&theme.lava, &palette, &theme.name),
+                chasm: tile_style(&theme.chasm, &palette, &theme.name),
+                name: theme.name,
+                min_depth: theme.min_depth,
+                max_depth: theme.max_depth,
+                ambient_light: theme.ambient_light,
+                connected_walls: theme.connected_walls,
+            })
+            .collect();
+
+        Themes { themes }
+    }
+
+    /// Returns the theme covering a depth, falling back to the first theme.
+    pub fn for_depth(&self, depth: i32) -> &Theme {
+        self.themes
+            .iter()
+            .find(|theme| {
+                depth >= theme.min_depth && theme.max_depth.map_or(true, |max| depth <= max)
+            })
+            .unwrap_or(&self.themes[0])
+    }
+}
+
+impl Theme {
+    /// Glyph and foreground colour used to draw a tile.
+    pub fn tile_glyph(&self, map: &Map, idx: usize) -> (u8, RGB) {
+        match map.tiles[idx] {
+            TileType::Floor => (self.floor.glyph, self.floor.fg),
+            TileType::Wall => {
+                let glyph = if self.connected_walls {
+                    connected_wall_glyph(map, idx)
+                } else {
+                    self.wall.glyph
+                };
+                (glyph, self.wall.fg)
+            }
+            TileType::DownStairs => (self.down_stairs.glyph, self.down_stairs.fg),
+            TileType::WoodenDoor => (self.door.glyph, self.door.fg),
+            TileType::Barricade => (self.barricade.glyph, self.barricade.fg),
+            TileType::ShallowWater => (self.shallow_water.glyph, self.shallow_water.fg),
+            TileType::DeepWater => (self.deep_water.glyph, self.deep_water.fg),
+            TileType::Lava => (self.lava.glyph, self.lava.fg),
+            TileType::Chasm => (self.chasm.glyph, self.chasm.fg),
+        }
+    }
+}
+
+/// Picks a box-drawing glyph based on which neighbouring walls the player has already seen.
+fn connected_wall_glyph(map: &Map, idx: usize) -> u8 {
+    let pos = map.pos_from_idx(idx);
+    let mut mask = 0;
+    if is_revealed_wall(map, pos.x, pos.y - 1) {
+        mask += 1;
+    }
+    if is_revealed_wall(map, pos.x, pos.y + 1) {
+        mask += 2;
+    }
+    if is_revealed_wall(map, pos.x - 1, pos.y) {
+        mask += 4;
+    }
+    if is_revealed_wall(map, pos.x + 1, pos.y) {
+        mask += 8;
+    }
+
+    match mask {
+        0 => 9,            // Pillar
+        1 | 2 | 3 => 186,  // North and/or south
+        4 | 8 | 12 => 205, // West and/or east
+        5 => 188,          // North and west
+        6 => 187,          // South and west
+        7 => 185,          // North, south and west
+        9 => 200,          // North and east
+        10 => 201,         // South and east
+        11 => 204,         // North, south and east
+        13 => 202,         // North, west and east
+        14 => 203,         // South, west and east
+        _ => 206,          // All four
+    }
+}
+
+fn is_revealed_wall(map: &Map, x: i32, y: i32) -> bool {
+    if x < 0 || x >= map.width || y < 0 || y >= map.height {
+        return false;
+    }
+    let idx = map.xy_idx(x, y);
+    map.tiles[idx] == TileType::Wall && map.revealed_tiles[idx]
+}
+
+fn tile_style(data: &TileStyleData, palette: &[(u8, u8, u8)], theme: &str) -> TileStyle {
+    let colour = parse_hex(&data.colour)
+        .unwrap_or_else(|| panic!("{}: invalid colour {}", theme, data.colour));
+    if !palette.contains(&colour) {
+        panic!("{}: colour {} is not in palette.css", theme, data.colour);
+    }
+
+    TileStyle {
+        glyph: rltk::to_cp437(data.glyph),
+        fg: RGB::from_u8(colour.0, colour.1, colour.2),
+    }
+}
+
+/// The colour for a `#rrggbb` string, as long as it's one of the palette's.
+pub fn palette_colour(hex: &str) -> Option<RGB> {
+    let colour = parse_hex(hex)?;
+    if parse_palette(PALETTE).contains(&colour) {
+        Some(RGB::from_u8(colour.0, colour.1, colour.2))
+    } else {
+        None
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<(u8, u8, u8)> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Reads the `rgb(r%, g%, b%)` entries of the palette stylesheet.
+fn parse_palette(css: &str) -> Vec<(u8, u8, u8)> {
+    css.lines()
+        .filter_map(|line| {
+            let start = line.find("rgb(")? + 4;
+            let end = line[start..].find(')')? + start;
+            let channels: Vec<u8> = line[start..end]
+                .split(',')
+                .map(|percent| {
+                    let percent: f32 = percent.trim().trim_end_matches('%').parse().ok()?;
+                    Some((percent * 2.55).round() as u8)
+                })
+                .collect::<Option<Vec<u8>>>()?;
+            match channels.as_slice() {
+                [r, g, b] => Some((*r, *g, *b)),
+                _ => None,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,35 @@
+use specs::prelude::*;
+
+/// How far, in tiles, each kind of noise carries.
+pub const FOOTSTEPS_VOLUME: i32 = 2;
+pub const RUNNING_VOLUME: i32 = 8;
+pub const DOOR_VOLUME: i32 = 6;
+pub const DIGGING_VOLUME: i32 = 8;
+pub const COMBAT_VOLUME: i32 = 8;
+pub const SMASHING_VOLUME: i32 = 10;
+pub const EXPLOSION_VOLUME: i32 = 14;
+
+/// A sound made somewhere on the map, by `maker` if anyone.
+pub struct Noise {
+    pub idx: usize,
+    pub volume: i32,
+    pub maker: Option<Entity>,
+}
+
+/// Noises made since monsters last listened.
+#[derive(Default)]
+pub struct Noises {
+    pub pending: Vec<Noise>,
+}
+
+impl Noises {
+    pub fn new() -> Noises {
+        Noises {
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn make(&mut self, idx: usize, volume: i32, maker: Option<Entity>) {
+        self.pending.push(Noise { idx, volume, maker });
+    }
+}
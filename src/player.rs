@@ -1,18 +1,31 @@
+use super::dijkstra::DijkstraMap;
+use super::factions::PLAYER_FACTION;
+use super::noise::{
+    Noises, DIGGING_VOLUME, DOOR_VOLUME, FOOTSTEPS_VOLUME, RUNNING_VOLUME, SMASHING_VOLUME,
+};
 use super::{
-    CombatStats, GameLog, Item, Map, Player, Position, RunState, State, TileType, Viewshed,
-    WantsToMelee, WantsToPickupItem, Monster,
+    CombatStats, Digger, Faction, GameLog, Healer, Heavy, HungerClock, HungerState, InBackpack,
+    Item, Map, Monster, Name, Npc, Player, Position, RunState, State, StatusEffect, StatusKind,
+    TileType, Viewshed, WantsToMelee, WantsToPickupItem,
 };
-use rltk::{console, Point, Rltk, VirtualKeyCode};
+use rltk::{console, Point, RandomNumberGenerator, Rltk, VirtualKeyCode};
 use specs::prelude::*;
 
-fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World) {
+fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World) -> RunState {
+    let (delta_x, delta_y) = stagger_if_confused(world, delta_x, delta_y);
     let players = world.read_storage::<Player>();
     let mut positions = world.write_storage::<Position>();
     let mut viewsheds = world.write_storage::<Viewshed>();
-    let combat_stats = world.read_storage::<CombatStats>();
-    let map = world.fetch::<Map>();
+    let mut combat_stats = world.write_storage::<CombatStats>();
+    let npcs = world.read_storage::<Npc>();
+    let factions = world.read_storage::<Faction>();
+    let mut map = world.fetch_mut::<Map>();
     let mut wants_to_melee = world.write_storage::<WantsToMelee>();
     let entities = world.entities();
+    let mut changed_tiles = Vec::new();
+    let mut swapped_ally = None;
+    let mut noise = None;
+    let mut result = RunState::PlayerTurn;
 
     for (entity, _player, pos, viewshed) in
         (&entities, &players, &mut positions, &mut viewsheds).join()
@@ -20,12 +33,60 @@ fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World) {
         let destination_x = pos.x + delta_x;
         let destination_y = pos.y + delta_y;
         if !map.is_inside_map(destination_x, destination_y) {
-            return;
+            return result;
         }
 
         let destination_idx = map.xy_idx(destination_x, destination_y);
         for potential_target in &map.tile_content[destination_idx] {
+            if let Some(npc) = npcs.get(*potential_target) {
+                talk_to_npc(world, entity, *potential_target, npc, &mut combat_stats);
+                return result;
+            }
+
+            // Allies step aside rather than get hit
+            let is_ally = factions
+                .get(*potential_target)
+                .map_or(false, |faction| faction.name == PLAYER_FACTION);
+            if is_ally {
+                // Swapping places puts each of them on the other's tile, deep water included
+                let here_idx = map.xy_idx(pos.x, pos.y);
+                let swimmer = if map.tiles[destination_idx] == TileType::DeepWater {
+                    Some(entity)
+                } else if map.tiles[here_idx] == TileType::DeepWater {
+                    Some(*potential_target)
+                } else {
+                    None
+                };
+                if let Some(swimmer) = swimmer.filter(|swimmer| carries::<Heavy>(world, *swimmer)) {
+                    let message = if swimmer == entity {
+                        "Your gear is too heavy to swim with.".to_string()
+                    } else {
+                        let names = world.read_storage::<Name>();
+                        let name = names.get(swimmer).map_or("ally", |name| &name.name);
+                        format!("The {}'s gear is too heavy to swim with.", name)
+                    };
+                    world.fetch_mut::<GameLog>().entries.push(message);
+                    return RunState::AwaitingInput;
+                }
+
+                swapped_ally = Some((*potential_target, pos.x, pos.y));
+                pos.x = destination_x;
+                pos.y = destination_y;
+                viewshed.dirty = true;
+                let mut player_position = world.write_resource::<Point>();
+                player_position.x = pos.x;
+                player_position.y = pos.y;
+                break;
+            }
+
             let target = combat_stats.get(*potential_target);
+            if target.is_some() && player_is_under(world, StatusKind::Fear) {
+                world
+                    .fetch_mut::<GameLog>()
+                    .entries
+                    .push("You are too frightened to attack!".to_string());
+                return RunState::AwaitingInput;
+            }
             if target.is_some() {
                 console::log(&format!("wanting to melee"));
                 wants_to_melee
@@ -36,20 +97,201 @@ fn try_move_player(delta_x: i32, delta_y: i32, world: &mut World) {
                         },
                     )
                     .expect("add target failed");
-                return;
+                return result;
             }
         }
+        if swapped_ally.is_some() {
+            continue;
+        }
+
+        if map.is_diggable(destination_idx) && carries::<Digger>(world, entity) {
+            map.change_tile(destination_idx, TileType::Floor);
+            changed_tiles.push(destination_idx);
+            viewshed.dirty = true;
+            noise = Some((entity, destination_idx, DIGGING_VOLUME));
+            world
+                .fetch_mut::<GameLog>()
+                .entries
+                .push("You dig through the wall.".to_string());
+        } else if map.tiles[destination_idx] == TileType::Barricade {
+            smash_barricade(world, &mut map, destination_idx, &mut changed_tiles);
+            viewshed.dirty = true;
+            noise = Some((entity, destination_idx, SMASHING_VOLUME));
+        } else if !map.blocked_tiles[destination_idx] {
+            let destination = map.tiles[destination_idx].clone();
+            if destination == TileType::DeepWater && carries::<Heavy>(world, entity) {
+                world
+                    .fetch_mut::<GameLog>()
+                    .entries
+                    .push("Your gear is too heavy to swim with.".to_string());
+                return RunState::AwaitingInput;
+            }
+
+            let current_idx = map.xy_idx(pos.x, pos.y);
+            if map.tiles[current_idx] == TileType::ShallowWater
+                && world
+                    .write_resource::<RandomNumberGenerator>()
+                    .roll_dice(1, 2)
+                    == 1
+            {
+                world
+                    .fetch_mut::<GameLog>()
+                    .entries
+                    .push("You wade slowly through the water.".to_string());
+                continue;
+            }
 
-        if !map.blocked_tiles[destination_idx] {
             pos.x = destination_x;
             pos.y = destination_y;
             viewshed.dirty = true;
+            let volume = if destination == TileType::WoodenDoor {
+                DOOR_VOLUME
+            } else {
+                FOOTSTEPS_VOLUME
+            };
+            noise = Some((entity, destination_idx, volume));
 
             let mut player_position = world.write_resource::<Point>();
             player_position.x = pos.x;
             player_position.y = pos.y;
+
+            if destination == TileType::Chasm {
+                result = RunState::FallToNextLevel;
+            }
+        }
+    }
+
+    if let Some((ally, x, y)) = swapped_ally {
+        if let Some(ally_pos) = positions.get_mut(ally) {
+            ally_pos.x = x;
+            ally_pos.y = y;
+        }
+        if let Some(ally_viewshed) = viewsheds.get_mut(ally) {
+            ally_viewshed.dirty = true;
+        }
+    }
+
+    if !changed_tiles.is_empty() {
+        map.dirty_viewsheds_seeing(&mut viewsheds, &changed_tiles);
+    }
+    if let Some((maker, idx, volume)) = noise {
+        world.fetch_mut::<Noises>().make(idx, volume, Some(maker));
+    }
+    result
+}
+
+/// Running covers two tiles in a turn, but everyone nearby hears it.
+fn run_player(delta_x: i32, delta_y: i32, world: &mut World) -> RunState {
+    let start = *world.fetch::<Point>();
+    let result = try_move_player(delta_x, delta_y, world);
+    let after_first_step = *world.fetch::<Point>();
+    if result != RunState::PlayerTurn || after_first_step == start {
+        return result;
+    }
+
+    let result = try_move_player(delta_x, delta_y, world);
+    let player_pos = *world.fetch::<Point>();
+    let idx = world.fetch::<Map>().xy_idx(player_pos.x, player_pos.y);
+    let player_entity = *world.fetch::<Entity>();
+    world
+        .fetch_mut::<Noises>()
+        .make(idx, RUNNING_VOLUME, Some(player_entity));
+    result
+}
+
+/// A confused player goes the wrong way half the time.
+fn stagger_if_confused(world: &mut World, delta_x: i32, delta_y: i32) -> (i32, i32) {
+    if !player_is_under(world, StatusKind::Confusion) {
+        return (delta_x, delta_y);
+    }
+
+    let mut rng = world.write_resource::<RandomNumberGenerator>();
+    if rng.roll_dice(1, 2) == 1 {
+        return (delta_x, delta_y);
+    }
+    world
+        .fetch_mut::<GameLog>()
+        .entries
+        .push("You stumble around in confusion.".to_string());
+    const DIRECTIONS: [(i32, i32); 8] = [
+        (-1, -1),
+        (0, -1),
+        (1, -1),
+        (-1, 0),
+        (1, 0),
+        (-1, 1),
+        (0, 1),
+        (1, 1),
+    ];
+    DIRECTIONS[rng.range(0, 8) as usize]
+}
+
+/// Checks whether the player is under a status effect of this kind.
+pub fn player_is_under(world: &World, kind: StatusKind) -> bool {
+    let player_entity = *world.fetch::<Entity>();
+    world
+        .read_storage::<StatusEffect>()
+        .join()
+        .any(|effect| effect.target == player_entity && effect.kind == kind)
+}
+
+/// Checks whether anything in the owner's backpack has component `T`.
+fn carries<T: Component>(world: &World, owner: Entity) -> bool {
+    let items = world.read_storage::<T>();
+    let backpack = world.read_storage::<InBackpack>();
+    (&items, &backpack)
+        .join()
+        .any(|(_item, pack)| pack.owner == owner)
+}
+
+/// Barricades take a few kicks to break, so they can slow the player down but never trap them.
+fn smash_barricade(world: &World, map: &mut Map, idx: usize, changed_tiles: &mut Vec<usize>) {
+    let mut gamelog = world.fetch_mut::<GameLog>();
+    if world.write_resource::<RandomNumberGenerator>().roll_dice(1, 3) == 1 {
+        map.change_tile(idx, TileType::Floor);
+        changed_tiles.push(idx);
+        gamelog
+            .entries
+            .push("You smash the barricade to pieces.".to_string());
+    } else {
+        gamelog
+            .entries
+            .push("You kick at the barricade.".to_string());
+    }
+}
+
+/// Bumping into an npc starts a conversation instead of a fight. Healers also patch the player up.
+fn talk_to_npc(
+    world: &World,
+    player: Entity,
+    npc_entity: Entity,
+    npc: &Npc,
+    combat_stats: &mut WriteStorage<CombatStats>,
+) {
+    let names = world.read_storage::<Name>();
+    let healers = world.read_storage::<Healer>();
+    let mut gamelog = world.fetch_mut::<GameLog>();
+    let npc_name = &names.get(npc_entity).unwrap().name;
+
+    if healers.get(npc_entity).is_some() {
+        if let Some(stats) = combat_stats.get_mut(player) {
+            if stats.hp < stats.max_hp {
+                stats.hp = stats.max_hp;
+                gamelog
+                    .entries
+                    .push(format!("The {} tends to your wounds.", npc_name));
+                return;
+            }
         }
     }
+
+    if !npc.lines.is_empty() {
+        let mut rng = world.write_resource::<RandomNumberGenerator>();
+        let line = &npc.lines[rng.range(0, npc.lines.len() as i32) as usize];
+        gamelog
+            .entries
+            .push(format!("{} says: \"{}\"", npc_name, line));
+    }
 }
 
 fn get_item(world: &mut World) {
@@ -88,26 +330,37 @@ fn get_item(world: &mut World) {
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    let world = &mut gs.world;
+    // Holding shift runs instead of walking
+    let step = if ctx.shift {
+        run_player
+    } else {
+        try_move_player
+    };
+
     // Player movement
     match ctx.key {
         None => return RunState::AwaitingInput,
         Some(key) => match key {
-            VirtualKeyCode::Left | VirtualKeyCode::A => try_move_player(-1, 0, &mut gs.world),
-            VirtualKeyCode::Right | VirtualKeyCode::D => try_move_player(1, 0, &mut gs.world),
-            VirtualKeyCode::Up | VirtualKeyCode::W => try_move_player(0, -1, &mut gs.world),
-            VirtualKeyCode::Down | VirtualKeyCode::S => try_move_player(0, 1, &mut gs.world),
+            VirtualKeyCode::Left | VirtualKeyCode::A => return step(-1, 0, world),
+            VirtualKeyCode::Right | VirtualKeyCode::D => return step(1, 0, world),
+            VirtualKeyCode::Up | VirtualKeyCode::W => return step(0, -1, world),
+            VirtualKeyCode::Down | VirtualKeyCode::S => return step(0, 1, world),
             // Diagonals
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => try_move_player(1, -1, &mut gs.world),
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => try_move_player(-1, -1, &mut gs.world),
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => try_move_player(1, 1, &mut gs.world),
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.world),
+            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => return step(1, -1, world),
+            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => return step(-1, -1, world),
+            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => return step(1, 1, world),
+            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => return step(-1, 1, world),
             // Skip Turns
-            VirtualKeyCode::Numpad5 | VirtualKeyCode::Space => return skip_turn(&mut gs.world),
+            VirtualKeyCode::Numpad5 | VirtualKeyCode::Space => return skip_turn(world),
+            VirtualKeyCode::X => return auto_explore(world),
             // Interactions
-            VirtualKeyCode::G => get_item(&mut gs.world),
+            VirtualKeyCode::G => get_item(world),
             VirtualKeyCode::E => return RunState::ShowInventory,
             VirtualKeyCode::Q => return RunState::ShowDropItem,
-            VirtualKeyCode::Period => return try_next_level(&mut gs.world),
+            VirtualKeyCode::Period => return try_next_level(world),
+            // Debugging
+            VirtualKeyCode::F12 => return export_map(world),
             // Save and Quit
             VirtualKeyCode::Escape => return RunState::SaveGame,
             _ => return RunState::AwaitingInput,
@@ -131,31 +384,86 @@ pub fn try_next_level(world: &mut World) -> RunState {
     }
 }
 
+fn export_map(world: &World) -> RunState {
+    let path = format!("./map_depth_{}.txt", world.fetch::<Map>().depth);
+    let message = match crate::ascii_map::export_map(world, &path) {
+        Ok(()) => format!("Map written to {}.", path),
+        Err(e) => format!("Unable to write map: {}", e),
+    };
+    world.fetch_mut::<GameLog>().entries.push(message);
+    RunState::AwaitingInput
+}
+
+/// Waiting with no monsters about heals a little, unless the player is too hungry to rest.
 fn skip_turn(world: &mut World) -> RunState {
+    let player_entity = *world.fetch::<Entity>();
+    let hungry = match world.read_storage::<HungerClock>().get(player_entity) {
+        Some(clock) => clock.state == HungerState::Hungry || clock.state == HungerState::Starving,
+        None => false,
+    };
+    if !monster_in_view(world) && !hungry {
+        let mut health_components = world.write_storage::<CombatStats>();
+        let player_hp = health_components.get_mut(player_entity).unwrap();
+        player_hp.hp = i32::min(player_hp.hp + 1, player_hp.max_hp);
+    }
+
+    RunState::PlayerTurn
+}
+
+/// Takes one step towards the nearest tile the player hasn't seen yet.
+fn auto_explore(world: &mut World) -> RunState {
+    if monster_in_view(world) {
+        world
+            .fetch_mut::<GameLog>()
+            .entries
+            .push("You can't explore with enemies in view.".to_string());
+        return RunState::AwaitingInput;
+    }
+
+    let step = {
+        let map = world.fetch::<Map>();
+        let player_pos = world.fetch::<Point>();
+        let unexplored: Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| !map.revealed_tiles[*idx] && map.tiles[*idx].movement_cost().is_some())
+            .collect();
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+        DijkstraMap::new(&map, &unexplored)
+            .downhill_step(&map, player_idx)
+            .map(|idx| (map.pos_from_idx(idx), map.tiles[idx].is_hazardous()))
+            .map(|(pos, hazardous)| (pos.x - player_pos.x, pos.y - player_pos.y, hazardous))
+    };
+
+    let message = match step {
+        Some((delta_x, delta_y, false)) => return try_move_player(delta_x, delta_y, world),
+        Some((_delta_x, _delta_y, true)) => "The only way on is too dangerous to explore.",
+        None => "There is nothing left to explore.",
+    };
+    world
+        .fetch_mut::<GameLog>()
+        .entries
+        .push(message.to_string());
+    RunState::AwaitingInput
+}
+
+/// Whether the player can see a monster that isn't on their side.
+fn monster_in_view(world: &World) -> bool {
     let player_entity = world.fetch::<Entity>();
     let viewshed_components = world.read_storage::<Viewshed>();
     let monsters = world.read_storage::<Monster>();
-
+    let factions = world.read_storage::<Faction>();
     let map = world.fetch::<Map>();
 
-    let mut can_heal = true;
+    let player_pos = world.fetch::<Point>();
+
     let viewshed = viewshed_components.get(*player_entity).unwrap();
-    for tile in viewshed.visible_tiles.iter() {
+    viewshed.visible_tiles.iter().any(|tile| {
         let idx = map.xy_idx(tile.x, tile.y);
-        for entity_id in map.tile_content[idx].iter() {
-            let mob = monsters.get(*entity_id);
-            match mob {
-                None => {}
-                Some(_) => { can_heal = false; }
-            }
-        }
-    }
-
-    if can_heal {
-        let mut health_components = world.write_storage::<CombatStats>();
-        let player_hp = health_components.get_mut(*player_entity).unwrap();
-        player_hp.hp = i32::min(player_hp.hp + 1, player_hp.max_hp);
-    }
-
-    RunState::PlayerTurn
+        map.can_make_out(idx, *player_pos)
+            && map.tile_content[idx].iter().any(|entity| {
+                let ally = factions
+                    .get(*entity)
+                    .map_or(false, |faction| faction.name == PLAYER_FACTION);
+                monsters.get(*entity).is_some() && !ally
+            })
+    })
 }
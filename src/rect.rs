@@ -24,6 +24,11 @@ impl Rect {
         self.x1 <= other.x2 && self.x2 >= other.x1 && self.y1 <= other.y2 && self.y2 >= other.y1
     }
 
+    // Returns true if the point is on this room's floor
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x > self.x1 && x <= self.x2 && y > self.y1 && y <= self.y2
+    }
+
     pub fn center(&self) -> Position {
         Position {
             x: (self.x1 + self.x2 + 1) / 2,
@@ -75,12 +75,42 @@ pub fn save_game(world: &mut World) {
             Ranged,
             InflictsDamage,
             AreaOfEffect,
-            Confusion,
+            StatusEffect,
+            InflictsStatus,
             ProvidesHealing,
             InBackpack,
             WantsToPickupItem,
             WantsToUseItem,
             WantsToDropItem,
+            Npc,
+            Wanderer,
+            Healer,
+            DigsTerrain,
+            Digger,
+            DestroysTerrain,
+            Charges,
+            Heavy,
+            Burning,
+            LightSource,
+            Lifetime,
+            Faction,
+            Charm,
+            AiState,
+            Ability,
+            KeepsDistance,
+            Morale,
+            PackMember,
+            Stealth,
+            IdleBehaviour,
+            PicksUpItems,
+            Corpse,
+            Boss,
+            RunStats,
+            Attributes,
+            MeleeDamage,
+            Resistances,
+            HungerClock,
+            ProvidesFood,
             SerializationHelper
         );
     }
@@ -140,12 +170,42 @@ pub fn load_game(ecs: &mut World) {
             Ranged,
             InflictsDamage,
             AreaOfEffect,
-            Confusion,
+            StatusEffect,
+            InflictsStatus,
             ProvidesHealing,
             InBackpack,
             WantsToPickupItem,
             WantsToUseItem,
             WantsToDropItem,
+            Npc,
+            Wanderer,
+            Healer,
+            DigsTerrain,
+            Digger,
+            DestroysTerrain,
+            Charges,
+            Heavy,
+            Burning,
+            LightSource,
+            Lifetime,
+            Faction,
+            Charm,
+            AiState,
+            Ability,
+            KeepsDistance,
+            Morale,
+            PackMember,
+            Stealth,
+            IdleBehaviour,
+            PicksUpItems,
+            Corpse,
+            Boss,
+            RunStats,
+            Attributes,
+            MeleeDamage,
+            Resistances,
+            HungerClock,
+            ProvidesFood,
             SerializationHelper
         );
     }
@@ -160,6 +220,7 @@ pub fn load_game(ecs: &mut World) {
             let mut worldmap = ecs.write_resource::<map::Map>();
             *worldmap = h.map.clone();
             worldmap.tile_content = vec![Vec::new(); map::MAP_SIZE];
+            worldmap.light = vec![map::FULL_LIGHT; map::MAP_SIZE];
             deleteme = Some(e);
         }
         for (e, _p, pos) in (&entities, &player, &position).join() {
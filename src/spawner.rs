@@ -1,7 +1,15 @@
+use crate::campaign::{AbilityData, Campaign};
+use crate::dijkstra::{DijkstraMap, UNREACHABLE};
+use crate::factions::PLAYER_FACTION;
+use crate::systems::{attribute_bonus, WELL_FED_TURNS};
 use crate::{
-    AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, InflictsDamage, Item, Map,
-    Monster, Name, Player, Position, ProvidesHealing, Ranged, Rect, Renderable, SerializeMe,
-    TileType, Viewshed,
+    map_builders, Ability, AiState, AreaOfEffect, Attributes, BlocksTile, Boss, Charges, Charm,
+    CombatStats, Consumable, Corpse, DamageType, DestroysTerrain, Digger, DigsTerrain, Faction,
+    Healer, Heavy, HungerClock, HungerState, IdleBehaviour, InBackpack, InflictsDamage,
+    InflictsStatus, Item, KeepsDistance, LightSource, Map, MeleeDamage, Monster, Morale, Name, Npc,
+    PackMember, PicksUpItems, Player, Position, ProvidesFood, ProvidesHealing, Ranged, Rect,
+    Renderable, Resistances, RunStats, SerializeMe, StatusKind, Stealth, TileType, Viewshed,
+    Wanderer,
 };
 use rltk::{RandomNumberGenerator, RGB};
 use specs::prelude::*;
@@ -11,10 +19,16 @@ const MAX_MONSTERS: i32 = 4;
 const MIN_MONSTERS: i32 = 0;
 const MAX_ITEMS: i32 = 2;
 const MIN_ITEMS: i32 = 0;
+const SPAWN_REGIONS: usize = 12;
+const DANGER_ZONES: i32 = 3;
+/// Health gained for every point of fitness bonus.
+const HEALTH_PER_FITNESS: i32 = 3;
+/// Monsters run once they're down to this percentage of their health.
+const MONSTER_FLEE_BELOW: i32 = 25;
 
-/// Spawns the player and returns his/her entity object.
+/// Spawns the player, carrying a torch, and returns his/her entity object.
 pub fn player(world: &mut World, initial_player_pos: Position) -> Entity {
-    world
+    let player = world
         .create_entity()
         .with(Player {})
         .with(initial_player_pos)
@@ -38,104 +52,575 @@ pub fn player(world: &mut World, initial_player_pos: Position) -> Entity {
             defense: 2,
             power: 5,
         })
+        .with(Faction {
+            name: PLAYER_FACTION.to_string(),
+        })
+        .with(Stealth { rating: 1 })
+        .with(RunStats::default())
+        .with(HungerClock {
+            state: HungerState::WellFed,
+            turns: WELL_FED_TURNS,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build()
+        .build();
+    physique(world, player, [12, 12, 12, 12], (1, 6));
+    torch(world, player);
+    player
+}
+
+/// Spawns monsters and items on a freshly built level.
+pub fn spawn_map(world: &mut World, map: &Map) {
+    let final_depth = world.fetch::<Campaign>().final_depth;
+    if map.depth == map_builders::TOWN_DEPTH {
+        spawn_town(world, map);
+    } else if map.depth == final_depth {
+        spawn_lair(world, map);
+    } else if map.rooms.is_empty() {
+        spawn_map_regions(world, map);
+    } else {
+        spawn_map_rooms(world, map);
+    }
 }
 
 pub fn spawn_map_rooms(world: &mut World, map: &Map) {
+    let distances = DijkstraMap::new(map, &[map.start_idx]);
     for room in map.rooms.iter().skip(1) {
-        spawn_room(world, room, map);
+        spawn_room(world, room, map, &distances);
+    }
+}
+
+/// Levels without rooms (caves) are split into Voronoi regions, each spawned like a room.
+pub fn spawn_map_regions(world: &mut World, map: &Map) {
+    let regions = {
+        let mut rng = world.write_resource::<RandomNumberGenerator>();
+        map_builders::voronoi_regions(map, &mut rng, SPAWN_REGIONS)
+    };
+
+    let distances = DijkstraMap::new(map, &[map.start_idx]);
+    for region in regions.iter() {
+        if !region.contains(&map.start_idx) {
+            spawn_region(world, region, map, &distances);
+        }
+    }
+}
+
+/// Populates the town: the healer waits in the first building, villagers stroll around the
+/// others.
+pub fn spawn_town(world: &mut World, map: &Map) {
+    for (i, building) in map.rooms.iter().enumerate() {
+        let pos = building.center();
+        if i == 0 {
+            healer(world, pos);
+        } else {
+            villager(world, pos);
+        }
+    }
+}
+
+/// Puts the boss in the middle of its hall, the last room of the lair, with its escort spread
+/// around the hall as its pack.
+pub fn spawn_lair(world: &mut World, map: &Map) {
+    let hall = match map.rooms.last() {
+        Some(hall) => *hall,
+        None => return,
+    };
+    let boss_definition = world.fetch::<Campaign>().boss.clone();
+
+    let mut area = Vec::new();
+    for y in hall.y1 + 1..=hall.y2 {
+        for x in hall.x1 + 1..=hall.x2 {
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::Floor && idx != map.idx_from_pos(hall.center()) {
+                area.push(idx);
+            }
+        }
+    }
+    let escort_points = {
+        let mut rng = world.write_resource::<RandomNumberGenerator>();
+        pick_spawn_points(&mut rng, area, boss_definition.escort.len() as i32)
+    };
+
+    let mut pack = vec![boss(world, hall.center())];
+    for (name, idx) in boss_definition.escort.iter().zip(escort_points) {
+        if let Some(spawn) = monster_spawner(name) {
+            pack.push(spawn(world, map.pos_from_idx(idx)));
+        }
     }
+    form_packs(world, &pack);
 }
 
 /// Fills a room with stuff!
-pub fn spawn_room(world: &mut World, room: &Rect, map: &Map) {
-    let (monster_spawn_points, item_spawn_points) = {
+pub fn spawn_room(world: &mut World, room: &Rect, map: &Map, distances: &DijkstraMap) {
+    let mut area = Vec::new();
+    for y in room.y1 + 1..=room.y2 {
+        for x in room.x1 + 1..=room.x2 {
+            area.push(map.xy_idx(x, y));
+        }
+    }
+    spawn_region(world, &area, map, distances);
+}
+
+/// Fills an arbitrary set of tiles with stuff! Areas far from the start, going by `distances`,
+/// get more monsters.
+pub fn spawn_region(world: &mut World, area: &[usize], map: &Map, distances: &DijkstraMap) {
+    let danger = danger_zone(distances, area);
+    let (monster_spawn_points, item_spawn_points, light_spawn_points) = {
         let mut rng = world.write_resource::<RandomNumberGenerator>();
         (
-            generate_monsters_for_room(&mut rng, room, map),
-            generate_items_for_room(&mut rng, room, map),
+            generate_monsters_for_region(&mut rng, area, map, danger),
+            generate_items_for_region(&mut rng, area, map),
+            generate_lights_for_region(&mut rng, area, map),
         )
     };
 
+    let mut monsters = Vec::new();
     for idx in monster_spawn_points {
         let pos = map.pos_from_idx(idx);
-        random_monster(world, pos);
+        monsters.push(random_monster(world, pos));
     }
+    form_packs(world, &monsters);
 
     for idx in item_spawn_points {
         let pos = map.pos_from_idx(idx);
         random_item(world, pos);
     }
-}
 
-fn generate_monsters_for_room(
-    rng: &mut RandomNumberGenerator,
-    room: &Rect,
-    map: &Map,
-) -> Vec<usize> {
-    let mut monsters = Vec::new();
+    for idx in light_spawn_points {
+        let pos = map.pos_from_idx(idx);
+        glowing_fungus(world, pos);
+    }
+}
 
-    let num_monsters = rng.range(MIN_MONSTERS, MAX_MONSTERS + 1);
-
-    for _i in 0..num_monsters {
-        let mut added = false;
-        while !added {
-            let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-            let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-            let idx = (y * map.width as usize) + x;
-            if !monsters.contains(&idx) && map.tiles[idx] == TileType::Floor {
-                monsters.push(idx);
-                added = true;
+/// Monsters of the same faction spawned together band into a pack, following the first of them.
+fn form_packs(world: &mut World, monsters: &[Entity]) {
+    let factions = world.read_storage::<Faction>();
+    let mut pack_members = world.write_storage::<PackMember>();
+    let mut leaders: Vec<Entity> = Vec::new();
+    for monster in monsters.iter() {
+        let faction = match factions.get(*monster) {
+            Some(faction) => faction,
+            None => continue,
+        };
+        let leader = leaders
+            .iter()
+            .find(|leader| factions.get(**leader).map(|f| &f.name) == Some(&faction.name));
+        match leader {
+            Some(leader) => {
+                pack_members
+                    .insert(*monster, PackMember { leader: *leader })
+                    .expect("Unable to insert pack member");
             }
+            None => leaders.push(*monster),
         }
     }
+}
 
-    monsters
+/// How deep into the level an area is, from 0 near the start to `DANGER_ZONES - 1` in the
+/// stretch farthest away.
+fn danger_zone(distances: &DijkstraMap, area: &[usize]) -> i32 {
+    let farthest = match distances.farthest() {
+        Some(idx) => distances.distances[idx],
+        None => return 0,
+    };
+    let nearest = area
+        .iter()
+        .map(|idx| distances.distances[*idx])
+        .fold(UNREACHABLE, f32::min);
+    if nearest == UNREACHABLE || farthest <= 0.0 {
+        return 0;
+    }
+    let zone = (nearest / farthest * DANGER_ZONES as f32) as i32;
+    i32::min(zone, DANGER_ZONES - 1)
 }
 
-fn generate_items_for_room(rng: &mut RandomNumberGenerator, room: &Rect, map: &Map) -> Vec<usize> {
-    let mut items = Vec::new();
+fn generate_monsters_for_region(
+    rng: &mut RandomNumberGenerator,
+    area: &[usize],
+    map: &Map,
+    danger: i32,
+) -> Vec<usize> {
+    let floors: Vec<usize> = area
+        .iter()
+        .filter(|idx| map.tiles[**idx] == TileType::Floor)
+        .cloned()
+        .collect();
+    let num_monsters = rng.range(MIN_MONSTERS, MAX_MONSTERS + 1) + danger;
+
+    pick_spawn_points(rng, floors, num_monsters)
+}
 
+fn generate_items_for_region(
+    rng: &mut RandomNumberGenerator,
+    area: &[usize],
+    map: &Map,
+) -> Vec<usize> {
+    // Nothing is left lying on the stairs, where it would hide them
+    let safe_tiles: Vec<usize> = area
+        .iter()
+        .filter(|idx| {
+            let tile = &map.tiles[**idx];
+            tile.is_walkable() && !tile.is_hazardous() && *tile != TileType::DownStairs
+        })
+        .cloned()
+        .collect();
     let num_items = rng.range(MIN_ITEMS, MAX_ITEMS + 1);
 
-    for _i in 0..num_items {
-        let mut added = false;
-        while !added {
-            let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-            let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-            let idx = (y * map.width as usize) + x;
-            if !items.contains(&idx) {
-                items.push(idx);
-                added = true;
-            }
-        }
+    pick_spawn_points(rng, safe_tiles, num_items)
+}
+
+/// Now and then a patch of glowing fungus lights up part of a region.
+fn generate_lights_for_region(
+    rng: &mut RandomNumberGenerator,
+    area: &[usize],
+    map: &Map,
+) -> Vec<usize> {
+    if rng.roll_dice(1, 3) > 1 {
+        return Vec::new();
     }
+    let floors: Vec<usize> = area
+        .iter()
+        .filter(|idx| map.tiles[**idx] == TileType::Floor)
+        .cloned()
+        .collect();
 
-    items
+    pick_spawn_points(rng, floors, 1)
+}
+
+fn pick_spawn_points(
+    rng: &mut RandomNumberGenerator,
+    mut candidates: Vec<usize>,
+    count: i32,
+) -> Vec<usize> {
+    let mut points = Vec::new();
+    for _i in 0..count {
+        if candidates.is_empty() {
+            break;
+        }
+        let roll = rng.range(0, candidates.len() as i32) as usize;
+        points.push(candidates.remove(roll));
+    }
+    points
 }
 
 /// Spawns a random monster at a given location
-pub fn random_monster(world: &mut World, pos: Position) {
+pub fn random_monster(world: &mut World, pos: Position) -> Entity {
     let roll: i32;
     {
         let mut rng = world.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 2);
+        roll = rng.roll_dice(1, 5);
     }
     match roll {
         1 => orc(world, pos),
-        _ => goblin(world, pos),
+        2 => goblin(world, pos),
+        3 => goblin_archer(world, pos),
+        4 => orc_shaman(world, pos),
+        _ => rat(world, pos),
     }
 }
 
-fn orc(ecs: &mut World, pos: Position) {
-    monster(ecs, pos, rltk::to_cp437('o'), "Orc");
+/// The function that spawns the monster with this name, for monsters named in data files.
+pub fn monster_spawner(name: &str) -> Option<fn(&mut World, Position) -> Entity> {
+    match name {
+        "Orc" => Some(orc),
+        "Goblin" => Some(goblin),
+        "Goblin Archer" => Some(goblin_archer),
+        "Orc Shaman" => Some(orc_shaman),
+        "Rat" => Some(rat),
+        _ => None,
+    }
+}
+
+fn orc(ecs: &mut World, pos: Position) -> Entity {
+    let guard = IdleBehaviour::Guard { x: pos.x, y: pos.y };
+    let orc = monster(ecs, pos, rltk::to_cp437('o'), "Orc", "Orcs", guard);
+    physique(ecs, orc, [14, 12, 8, 8], (1, 6));
+    resistances(
+        ecs,
+        orc,
+        Resistances {
+            resistant: vec![DamageType::Poison],
+            ..Resistances::default()
+        },
+    );
+    picks_up_items(ecs, orc);
+    orc
 }
-fn goblin(ecs: &mut World, pos: Position) {
-    monster(ecs, pos, rltk::to_cp437('g'), "Goblin");
+fn goblin(ecs: &mut World, pos: Position) -> Entity {
+    let patrol = IdleBehaviour::Patrol {
+        waypoints: Vec::new(),
+        next: 0,
+    };
+    let goblin = monster(ecs, pos, rltk::to_cp437('g'), "Goblin", "Goblins", patrol);
+    physique(ecs, goblin, [8, 10, 14, 10], (1, 6));
+    picks_up_items(ecs, goblin);
+    goblin
+}
+fn rat(ecs: &mut World, pos: Position) -> Entity {
+    let wander = IdleBehaviour::Wander { x: pos.x, y: pos.y };
+    let rat = monster(ecs, pos, rltk::to_cp437('r'), "Rat", "Animals", wander);
+    physique(ecs, rat, [6, 8, 14, 2], (1, 3));
+    resistances(
+        ecs,
+        rat,
+        Resistances {
+            vulnerable: vec![DamageType::Fire],
+            ..Resistances::default()
+        },
+    );
+    ecs.write_storage::<Morale>()
+        .insert(
+            rat,
+            Morale {
+                flee_below: MONSTER_FLEE_BELOW,
+                cowardly: true,
+                shaken: 0,
+            },
+        )
+        .expect("Unable to insert morale");
+    rat
+}
+fn goblin_archer(ecs: &mut World, pos: Position) -> Entity {
+    let guard = IdleBehaviour::Guard { x: pos.x, y: pos.y };
+    let glyph = rltk::to_cp437('a');
+    let archer = monster(ecs, pos, glyph, "Goblin Archer", "Goblins", guard);
+    physique(ecs, archer, [8, 10, 14, 10], (1, 4));
+    keep_distance(ecs, archer, 3);
+    picks_up_items(ecs, archer);
+    ability(ecs, archer, "Arrow", 2)
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage {
+            damage: 4,
+            damage_type: DamageType::Physical,
+        })
+        .build();
+    ability(ecs, archer, "Poisoned Arrow", 8)
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage {
+            damage: 2,
+            damage_type: DamageType::Physical,
+        })
+        .with(InflictsStatus {
+            kind: StatusKind::Poison,
+            turns: 4,
+            potency: 2,
+        })
+        .build();
+    archer
+}
+fn orc_shaman(ecs: &mut World, pos: Position) -> Entity {
+    let wander = IdleBehaviour::Wander { x: pos.x, y: pos.y };
+    let shaman = monster(ecs, pos, rltk::to_cp437('s'), "Orc Shaman", "Orcs", wander);
+    physique(ecs, shaman, [10, 10, 10, 14], (1, 4));
+    resistances(
+        ecs,
+        shaman,
+        Resistances {
+            resistant: vec![DamageType::Fire, DamageType::Poison],
+            ..Resistances::default()
+        },
+    );
+    keep_distance(ecs, shaman, 4);
+    picks_up_items(ecs, shaman);
+    ability(ecs, shaman, "Firebolt", 3)
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage {
+            damage: 6,
+            damage_type: DamageType::Fire,
+        })
+        .build();
+    ability(ecs, shaman, "Flame Burst", 8)
+        .with(Ranged { range: 5 })
+        .with(InflictsDamage {
+            damage: 8,
+            damage_type: DamageType::Fire,
+        })
+        .with(AreaOfEffect { radius: 1 })
+        .build();
+    ability(ecs, shaman, "Befuddle", 10)
+        .with(Ranged { range: 6 })
+        .with(InflictsStatus {
+            kind: StatusKind::Confusion,
+            turns: 3,
+            potency: 0,
+        })
+        .build();
+    ability(ecs, shaman, "Hex", 8)
+        .with(Ranged { range: 6 })
+        .with(InflictsStatus {
+            kind: StatusKind::Slow,
+            turns: 4,
+            potency: 0,
+        })
+        .build();
+    ability(ecs, shaman, "Mend", 6)
+        .with(Ranged { range: 6 })
+        .with(ProvidesHealing { heal_amount: 8 })
+        .build();
+    shaman
+}
+
+/// Starts building an ability for `owner`; add its effects and build it.
+fn ability<'a>(ecs: &'a mut World, owner: Entity, name: &str, cooldown: i32) -> EntityBuilder<'a> {
+    ecs.create_entity()
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(Ability {
+            owner,
+            cooldown,
+            ready_in: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+}
+
+/// Spawns the boss described in the campaign data, with the abilities it starts the fight with.
+fn boss(ecs: &mut World, pos: Position) -> Entity {
+    let definition = ecs.fetch::<Campaign>().boss.clone();
+    let boss = ecs
+        .create_entity()
+        .with(pos.clone())
+        .with(Renderable {
+            glyph: definition.glyph,
+            fg: definition.fg,
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: 8,
+            dirty: true,
+        })
+        .with(Monster {})
+        .with(Name {
+            name: definition.name.clone(),
+        })
+        .with(BlocksTile {})
+        .with(CombatStats {
+            max_hp: definition.max_hp,
+            hp: definition.max_hp,
+            defense: definition.defense,
+            power: definition.power,
+        })
+        .with(Faction {
+            name: definition.faction.clone(),
+        })
+        .with(AiState::Idle)
+        .with(IdleBehaviour::Guard { x: pos.x, y: pos.y })
+        .with(Boss { phase: 0 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+    physique(ecs, boss, definition.attributes, definition.melee_damage);
+    resistances(ecs, boss, definition.resistances);
+    for ability in definition.abilities.iter() {
+        boss_ability(ecs.create_entity(), boss, ability);
+    }
+    boss
 }
 
-fn monster<S: ToString>(world: &mut World, pos: Position, glyph: u8, name: S) -> Entity {
+/// Builds an ability described in the campaign data for `owner`. Takes any builder, so phases
+/// of the boss fight can hand out new abilities from within a system.
+pub fn boss_ability<B: Builder + MarkedBuilder>(
+    builder: B,
+    owner: Entity,
+    data: &AbilityData,
+) -> Entity {
+    let mut builder = builder
+        .with(Name {
+            name: data.name.clone(),
+        })
+        .with(Ability {
+            owner,
+            cooldown: data.cooldown,
+            ready_in: 0,
+        });
+    if let Some(range) = data.range {
+        builder = builder.with(Ranged { range });
+    }
+    if let Some(damage) = data.damage {
+        builder = builder.with(InflictsDamage {
+            damage,
+            damage_type: data.damage_type.unwrap_or(DamageType::Physical),
+        });
+    }
+    if let Some(radius) = data.radius {
+        builder = builder.with(AreaOfEffect { radius });
+    }
+    if let Some(status) = &data.status {
+        builder = builder.with(status.clone());
+    }
+    if let Some(heal_amount) = data.heal {
+        builder = builder.with(ProvidesHealing { heal_amount });
+    }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+/// Gives a creature its might, fitness, quickness and intelligence, in that order, and the dice it
+/// hits with in melee. Fitness adds to or takes away from its health.
+fn physique(ecs: &mut World, creature: Entity, scores: [i32; 4], damage: (i32, i32)) {
+    let [might, fitness, quickness, intelligence] = scores;
+    ecs.write_storage::<Attributes>()
+        .insert(
+            creature,
+            Attributes {
+                might,
+                fitness,
+                quickness,
+                intelligence,
+            },
+        )
+        .expect("Unable to insert attributes");
+    ecs.write_storage::<MeleeDamage>()
+        .insert(
+            creature,
+            MeleeDamage {
+                dice: damage.0,
+                sides: damage.1,
+            },
+        )
+        .expect("Unable to insert melee damage");
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(creature) {
+        stats.max_hp = i32::max(
+            1,
+            stats.max_hp + attribute_bonus(fitness) * HEALTH_PER_FITNESS,
+        );
+        stats.hp = stats.max_hp;
+    }
+}
+
+fn keep_distance(ecs: &mut World, monster: Entity, distance: i32) {
+    ecs.write_storage::<KeepsDistance>()
+        .insert(monster, KeepsDistance { distance })
+        .expect("Unable to insert keeps distance");
+}
+
+fn resistances(ecs: &mut World, creature: Entity, resistances: Resistances) {
+    ecs.write_storage::<Resistances>()
+        .insert(creature, resistances)
+        .expect("Unable to insert resistances");
+}
+
+fn picks_up_items(ecs: &mut World, monster: Entity) {
+    ecs.write_storage::<PicksUpItems>()
+        .insert(monster, PicksUpItems {})
+        .expect("Unable to insert picks up items");
+}
+
+fn monster<S: ToString>(
+    world: &mut World,
+    pos: Position,
+    glyph: u8,
+    name: S,
+    faction: &str,
+    idle: IdleBehaviour,
+) -> Entity {
+    let roll = world
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(1, 2);
+    let state = if roll == 1 {
+        AiState::Asleep
+    } else {
+        AiState::Idle
+    };
     world
         .create_entity()
         .with(pos)
@@ -161,24 +646,114 @@ fn monster<S: ToString>(world: &mut World, pos: Position, glyph: u8, name: S) ->
             defense: 1,
             power: 3,
         })
+        .with(Faction {
+            name: faction.to_string(),
+        })
+        .with(state)
+        .with(idle)
+        .with(Morale {
+            flee_below: MONSTER_FLEE_BELOW,
+            cowardly: false,
+            shaken: 0,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
+fn healer(world: &mut World, pos: Position) {
+    world
+        .create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::from_f32(0.388, 0.78, 0.302),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Healer".to_string(),
+        })
+        .with(Npc {
+            lines: vec![
+                "Come in, come in. Let me see those wounds.".to_string(),
+                "Mind the goblins down there.".to_string(),
+            ],
+        })
+        .with(Healer {})
+        .with(BlocksTile {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn villager(world: &mut World, pos: Position) {
+    world
+        .create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::from_f32(0.894, 0.651, 0.447),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Villager".to_string(),
+        })
+        .with(Npc {
+            lines: vec![
+                "Lovely weather today.".to_string(),
+                "They say the caves go down forever.".to_string(),
+                "Nobody who went down the stairs came back.".to_string(),
+            ],
+        })
+        .with(Wanderer {})
+        .with(BlocksTile {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
 pub fn random_item(world: &mut World, pos: Position) {
     let roll: i32;
     {
         let mut rng = world.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 4);
+        roll = rng.roll_dice(1, 18);
     }
     match roll {
-        1 => health_potion(world, pos),
-        2 => fireball_scroll(world, pos),
-        3 => confusion_scroll(world, pos),
+        1 | 2 => health_potion(world, pos),
+        3 | 4 => fireball_scroll(world, pos),
+        5 | 6 => confusion_scroll(world, pos),
+        7 => wand_of_digging(world, pos),
+        8 => pickaxe(world, pos),
+        9 => charm_scroll(world, pos),
+        10 => sleep_scroll(world, pos),
+        11 => fear_scroll(world, pos),
+        12 => haste_potion(world, pos),
+        13 => regeneration_potion(world, pos),
+        14 => flash_powder(world, pos),
+        15 | 16 => ration(world, pos),
         _ => magic_missile_scroll(world, pos),
     }
 }
 
+/// The function that spawns the item with this name, for items named in data files.
+pub fn item_spawner(name: &str) -> Option<fn(&mut World, Position)> {
+    match name {
+        "Health Potion" => Some(health_potion),
+        "Magic Missile Scroll" => Some(magic_missile_scroll),
+        "Fireball Scroll" => Some(fireball_scroll),
+        "Confusion Scroll" => Some(confusion_scroll),
+        "Charm Scroll" => Some(charm_scroll),
+        "Sleep Scroll" => Some(sleep_scroll),
+        "Fear Scroll" => Some(fear_scroll),
+        "Haste Potion" => Some(haste_potion),
+        "Regeneration Potion" => Some(regeneration_potion),
+        "Flash Powder" => Some(flash_powder),
+        "Ration" => Some(ration),
+        "Wand of Digging" => Some(wand_of_digging),
+        "Pick-axe" => Some(pickaxe),
+        _ => None,
+    }
+}
+
 fn health_potion(ecs: &mut World, pos: Position) {
     ecs.create_entity()
         .with(pos)
@@ -213,7 +788,10 @@ fn magic_missile_scroll(ecs: &mut World, pos: Position) {
         .with(Item {})
         .with(Consumable {})
         .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 8 })
+        .with(InflictsDamage {
+            damage: 8,
+            damage_type: DamageType::Arcane,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 }
@@ -233,8 +811,12 @@ fn fireball_scroll(ecs: &mut World, pos: Position) {
         .with(Item {})
         .with(Consumable {})
         .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 20 })
+        .with(InflictsDamage {
+            damage: 20,
+            damage_type: DamageType::Fire,
+        })
         .with(AreaOfEffect { radius: 3 })
+        .with(DestroysTerrain {})
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 }
@@ -255,7 +837,273 @@ fn confusion_scroll(ecs: &mut World, pos: Position) {
         .with(Consumable {})
         .with(Ranged { range: 6 })
         .with(AreaOfEffect { radius: 3 })
-        .with(Confusion { turns: 4 })
+        .with(InflictsStatus {
+            kind: StatusKind::Confusion,
+            turns: 4,
+            potency: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn charm_scroll(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('S'),
+            fg: RGB::from_f32(0.965, 0.459, 0.478),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Charm Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(Consumable {})
+        .with(Ranged { range: 6 })
+        .with(Charm {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn ration(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::GREEN),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Ration".to_string(),
+        })
+        .with(Item {})
+        .with(Consumable {})
+        .with(ProvidesFood {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn sleep_scroll(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('S'),
+            fg: RGB::from_f32(0.416, 0.353, 0.804),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Sleep Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(Consumable {})
+        .with(Ranged { range: 6 })
+        .with(AreaOfEffect { radius: 2 })
+        .with(InflictsStatus {
+            kind: StatusKind::Sleep,
+            turns: 8,
+            potency: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn fear_scroll(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('S'),
+            fg: RGB::from_f32(0.545, 0.0, 0.0),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Fear Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(Consumable {})
+        .with(Ranged { range: 6 })
+        .with(AreaOfEffect { radius: 2 })
+        .with(InflictsStatus {
+            kind: StatusKind::Fear,
+            turns: 6,
+            potency: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn haste_potion(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('¡'),
+            fg: RGB::named(rltk::YELLOW),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Haste Potion".to_string(),
+        })
+        .with(Item {})
+        .with(Consumable {})
+        .with(InflictsStatus {
+            kind: StatusKind::Haste,
+            turns: 10,
+            potency: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn regeneration_potion(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('¡'),
+            fg: RGB::named(rltk::GREEN),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Regeneration Potion".to_string(),
+        })
+        .with(Item {})
+        .with(Consumable {})
+        .with(InflictsStatus {
+            kind: StatusKind::Regeneration,
+            turns: 10,
+            potency: 2,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn flash_powder(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('*'),
+            fg: RGB::named(rltk::WHITE),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Flash Powder".to_string(),
+        })
+        .with(Item {})
+        .with(Consumable {})
+        .with(Ranged { range: 4 })
+        .with(AreaOfEffect { radius: 1 })
+        .with(InflictsStatus {
+            kind: StatusKind::Blindness,
+            turns: 6,
+            potency: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn wand_of_digging(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('/'),
+            fg: RGB::from_f32(0.91, 0.718, 0.588),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Wand of Digging".to_string(),
+        })
+        .with(Item {})
+        .with(Charges { remaining: 3 })
+        .with(Ranged { range: 6 })
+        .with(DigsTerrain {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn pickaxe(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('('),
+            fg: RGB::from_f32(0.753, 0.796, 0.863),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Pick-axe".to_string(),
+        })
+        .with(Item {})
+        .with(Digger {})
+        .with(Heavy {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn torch(ecs: &mut World, owner: Entity) {
+    ecs.create_entity()
+        .with(Renderable {
+            glyph: rltk::to_cp437('!'),
+            fg: RGB::from_f32(0.969, 0.463, 0.133),
+            bg: RGB::named(rltk::BLACK),
+            index: 10,
+        })
+        .with(Name {
+            name: "Torch".to_string(),
+        })
+        .with(Item {})
+        .with(LightSource {
+            colour: RGB::from_f32(0.996, 0.682, 0.204),
+            range: 5,
+        })
+        .with(InBackpack { owner })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/// Leaves the remains of a dead creature, drawn in its colour, where it fell. Hungry enough, you
+/// can eat them.
+pub fn corpse(ecs: &mut World, name: &str, fg: RGB, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg,
+            bg: RGB::named(rltk::BLACK),
+            index: 4,
+        })
+        .with(Name {
+            name: format!("{} corpse", name),
+        })
+        .with(Corpse {})
+        .with(Item {})
+        .with(Consumable {})
+        .with(ProvidesFood {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn glowing_fungus(ecs: &mut World, pos: Position) {
+    ecs.create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437('"'),
+            fg: RGB::from_f32(0.388, 0.78, 0.302),
+            bg: RGB::named(rltk::BLACK),
+            index: 5,
+        })
+        .with(Name {
+            name: "Glowing fungus".to_string(),
+        })
+        .with(LightSource {
+            colour: RGB::from_f32(0.243, 0.537, 0.282),
+            range: 3,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 }
@@ -0,0 +1,160 @@
+use super::{look_around, status_effects};
+use crate::dijkstra::DijkstraMap;
+use crate::factions::FactionTable;
+use crate::noise::Noises;
+use crate::{
+    AiState, CombatStats, Faction, GameLog, Map, Monster, Name, Position, RunState, StatusEffect,
+    Stealth, Viewshed,
+};
+use rltk::{Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+/// How much harder an enemy standing in the dark is to spot.
+const DARKNESS_PENALTY: i32 = 2;
+
+/// Works out what monsters notice. Noises carry through the map, waking sleepers and drawing
+/// anyone who hasn't found an enemy yet to investigate. Once a round, idle monsters may also
+/// spot an enemy they can see; stealthy enemies, far away or in the dark, often go unnoticed.
+/// Stealth also muffles the noises its owner makes. Monsters that are asleep or stunned notice
+/// nothing at all.
+pub struct Awareness {}
+
+impl<'a> System<'a> for Awareness {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Point>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, FactionTable>,
+        WriteExpect<'a, Noises>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        Entities<'a>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Stealth>,
+        WriteStorage<'a, AiState>,
+        ReadStorage<'a, StatusEffect>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            run_state,
+            player_pos,
+            player_entity,
+            faction_table,
+            mut noises,
+            mut gamelog,
+            mut rng,
+            entities,
+            monsters,
+            names,
+            positions,
+            viewsheds,
+            factions,
+            combat_stats,
+            stealth,
+            mut ai_states,
+            effects,
+        ) = data;
+
+        let afflictions = status_effects::afflictions(&effects);
+
+        for noise in std::mem::take(&mut noises.pending) {
+            let muffling = noise
+                .maker
+                .and_then(|maker| stealth.get(maker))
+                .map_or(0, |stealth| stealth.rating);
+            let volume = (noise.volume - muffling) as f32;
+            if volume <= 0.0 {
+                continue;
+            }
+
+            let sound = DijkstraMap::sound(&map, noise.idx);
+            let origin = map.pos_from_idx(noise.idx);
+            for (entity, _monster, pos, name, state) in
+                (&entities, &monsters, &positions, &names, &mut ai_states).join()
+            {
+                let idx = map.xy_idx(pos.x, pos.y);
+                if Some(entity) == noise.maker
+                    || sound.distances[idx] > volume
+                    || status_effects::is_helpless(&afflictions, entity)
+                {
+                    continue;
+                }
+                match state {
+                    AiState::Asleep => {
+                        if map.can_make_out(idx, *player_pos) {
+                            gamelog.entries.push(format!("The {} wakes up.", name.name));
+                        }
+                    }
+                    AiState::Idle | AiState::Searching { .. } => {}
+                    _ => continue,
+                }
+                *state = AiState::Hunting {
+                    x: origin.x,
+                    y: origin.y,
+                };
+            }
+        }
+
+        if *run_state != RunState::MonsterTurn {
+            return;
+        }
+
+        for (entity, _monster, pos, name, viewshed, faction, state) in (
+            &entities,
+            &monsters,
+            &positions,
+            &names,
+            &viewsheds,
+            &factions,
+            &mut ai_states,
+        )
+            .join()
+        {
+            if *state != AiState::Idle || status_effects::is_helpless(&afflictions, entity) {
+                continue;
+            }
+            let surroundings = look_around(
+                &map,
+                &faction_table,
+                &factions,
+                &combat_stats,
+                entity,
+                &faction.name,
+                Point::new(pos.x, pos.y),
+                viewshed,
+            );
+
+            for (enemy, enemy_idx, distance) in surroundings.enemies.iter() {
+                let mut difficulty = stealth.get(*enemy).map_or(0, |stealth| stealth.rating);
+                difficulty += *distance as i32 / 3;
+                if !map.is_lit(*enemy_idx) {
+                    difficulty += DARKNESS_PENALTY;
+                }
+                if rng.roll_dice(1, 10) <= difficulty {
+                    continue;
+                }
+
+                let spotted = map.pos_from_idx(*enemy_idx);
+                *state = AiState::Hunting {
+                    x: spotted.x,
+                    y: spotted.y,
+                };
+                let idx = map.xy_idx(pos.x, pos.y);
+                if *enemy == *player_entity && map.can_make_out(idx, *player_pos) {
+                    gamelog
+                        .entries
+                        .push(format!("The {} notices you!", name.name));
+                }
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::campaign::Campaign;
+use crate::{spawner, Boss, CombatStats, GameLog};
+use specs::prelude::*;
+
+/// Moves the boss fight on to its next phase once the boss is hurt badly enough: the boss gets
+/// stronger and learns new abilities, and the player is told.
+pub struct BossPhases {}
+
+impl<'a> System<'a> for BossPhases {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Campaign>,
+        WriteExpect<'a, GameLog>,
+        Read<'a, LazyUpdate>,
+        WriteStorage<'a, Boss>,
+        WriteStorage<'a, CombatStats>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, campaign, mut gamelog, lazy, mut bosses, mut combat_stats) = data;
+
+        let phases = &campaign.boss.phases;
+        for (entity, boss, stats) in (&entities, &mut bosses, &mut combat_stats).join() {
+            if stats.hp < 1 {
+                continue;
+            }
+            while let Some(phase) = phases.get(boss.phase) {
+                if stats.hp * 100 >= stats.max_hp * phase.below {
+                    break;
+                }
+                boss.phase += 1;
+
+                gamelog.entries.push(phase.message.clone());
+                stats.power += phase.power;
+                stats.defense += phase.defense;
+                for ability in phase.abilities.iter() {
+                    spawner::boss_ability(lazy.create_entity(&entities), entity, ability);
+                }
+            }
+        }
+    }
+}
@@ -1,31 +1,214 @@
-use crate::{CombatStats, GameLog, Name, Player, SufferDamage};
-use specs::{Entity, Join, System, World, WorldExt, WriteStorage};
+use crate::loot::LootTables;
+use crate::spawner;
+use crate::{
+    Ability, CombatStats, GameLog, InBackpack, Monster, Morale, Name, PackMember, Player, Position,
+    Renderable, Resistances, RunStats, StatusEffect, StatusKind, SufferDamage,
+};
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
 
+/// Applies every hit creatures took this turn, adjusted for their resistances, immunities and
+/// vulnerabilities. Anything hurt in its sleep wakes up, and a monster goes on the player's tally
+/// of kills if it was the player who dealt the blow that finished it.
 pub struct Damage {}
 
 impl<'a> System<'a> for Damage {
     type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Resistances>,
         WriteStorage<'a, CombatStats>,
         WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, StatusEffect>,
+        WriteStorage<'a, RunStats>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage) = data;
+        let (
+            entities,
+            player_entity,
+            mut log,
+            names,
+            monsters,
+            resistances,
+            mut stats,
+            mut damage,
+            effects,
+            mut run_stats,
+        ) = data;
 
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount;
+        let mut hurt = Vec::new();
+        let mut kills = 0;
+        for (entity, stats, damage) in (&entities, &mut stats, &damage).join() {
+            let resistances = resistances.get(entity).cloned().unwrap_or_default();
+            for (amount, damage_type, by_player) in damage.amounts.iter() {
+                let (taken, response) = if resistances.immune.contains(damage_type) {
+                    (0, "is immune to")
+                } else if resistances.resistant.contains(damage_type) {
+                    (amount / 2, "resists")
+                } else if resistances.vulnerable.contains(damage_type) {
+                    (amount * 2, "is vulnerable to")
+                } else {
+                    (*amount, "")
+                };
+                let was_alive = stats.hp > 0;
+                stats.hp -= taken;
+                if taken > 0 {
+                    hurt.push(entity);
+                }
+                if was_alive && stats.hp < 1 && *by_player && monsters.get(entity).is_some() {
+                    kills += 1;
+                }
+
+                if let (false, Some(name)) = (response.is_empty(), names.get(entity)) {
+                    log.entries.push(format!(
+                        "{} {} {} damage.",
+                        name.name,
+                        response,
+                        damage_type.name()
+                    ));
+                }
+            }
         }
 
         damage.clear();
+
+        if let Some(run_stats) = run_stats.get_mut(*player_entity) {
+            run_stats.kills += kills;
+        }
+
+        for (effect_entity, effect) in (&entities, &effects).join() {
+            if effect.kind == StatusKind::Sleep && hurt.contains(&effect.target) {
+                entities
+                    .delete(effect_entity)
+                    .expect("Unable to delete status effect");
+                if let Some(name) = names.get(effect.target) {
+                    log.entries.push(format!("{} wakes up!", name.name));
+                }
+            }
+        }
     }
 }
 
 pub fn delete_the_dead(world: &mut World) {
-    for victim in get_dead_entities(&world) {
+    let dead = get_dead_entities(&world);
+    scatter_packs(&world, &dead);
+    drop_belongings(&world, &dead);
+    leave_remains(world, &dead);
+    let abilities = get_abilities_of(&world, &dead);
+    let effects = get_effects_on(&world, &dead);
+    for victim in dead.into_iter().chain(abilities).chain(effects) {
         world.delete_entity(victim).expect("Unable to delete");
     }
 }
 
+/// How many turns a pack runs for after losing its leader.
+const SCATTER_TURNS: i32 = 10;
+
+/// Packs whose leader died break up, and the survivors run for a while.
+fn scatter_packs(world: &World, dead: &[Entity]) {
+    let entities = world.entities();
+    let mut pack_members = world.write_storage::<PackMember>();
+    let mut morale = world.write_storage::<Morale>();
+    let mut log = world.write_resource::<GameLog>();
+
+    let scattered: Vec<Entity> = (&entities, &pack_members)
+        .join()
+        .filter(|(_entity, member)| dead.contains(&member.leader))
+        .map(|(entity, _member)| entity)
+        .collect();
+    if scattered.iter().any(|member| !dead.contains(member)) {
+        log.entries
+            .push("With its leader dead, the pack scatters!".to_string());
+    }
+    for member in scattered {
+        pack_members.remove(member);
+        if let Some(morale) = morale.get_mut(member) {
+            morale.shaken = SCATTER_TURNS;
+        }
+    }
+}
+
+/// Whatever the dead were carrying falls to the floor where they died.
+fn drop_belongings(world: &World, dead: &[Entity]) {
+    let entities = world.entities();
+    let mut backpacks = world.write_storage::<InBackpack>();
+    let mut positions = world.write_storage::<Position>();
+
+    let dropped: Vec<(Entity, Position)> = (&entities, &backpacks)
+        .join()
+        .filter(|(_item, carried)| dead.contains(&carried.owner))
+        .filter_map(|(item, carried)| positions.get(carried.owner).map(|pos| (item, pos.clone())))
+        .collect();
+    for (item, pos) in dropped {
+        backpacks.remove(item);
+        positions.insert(item, pos).expect("Unable to drop item");
+    }
+}
+
+/// The dead leave a corpse and whatever their loot table drops where they fell.
+fn leave_remains(world: &mut World, dead: &[Entity]) {
+    let mut remains: Vec<(String, Position, RGB)> = Vec::new();
+    {
+        let names = world.read_storage::<Name>();
+        let positions = world.read_storage::<Position>();
+        let renderables = world.read_storage::<Renderable>();
+        for victim in dead.iter() {
+            if let (Some(name), Some(pos), Some(render)) = (
+                names.get(*victim),
+                positions.get(*victim),
+                renderables.get(*victim),
+            ) {
+                remains.push((name.name.clone(), pos.clone(), render.fg));
+            }
+        }
+    }
+
+    for (name, pos, fg) in remains {
+        let (corpse, drops) = {
+            let loot_tables = world.fetch::<LootTables>();
+            let mut rng = world.write_resource::<RandomNumberGenerator>();
+            match loot_tables.get(&name) {
+                Some(table) => (table.corpse, table.roll(&mut rng)),
+                None => continue,
+            }
+        };
+        if corpse {
+            spawner::corpse(world, &name, fg, pos.clone());
+        }
+        for item in drops {
+            if let Some(spawn) = spawner::item_spawner(&item) {
+                spawn(world, pos.clone());
+            }
+        }
+    }
+}
+
+/// Abilities die with their owner.
+fn get_abilities_of(world: &World, owners: &[Entity]) -> Vec<Entity> {
+    let abilities = world.read_storage::<Ability>();
+    let entities = world.entities();
+    (&entities, &abilities)
+        .join()
+        .filter(|(_entity, ability)| owners.contains(&ability.owner))
+        .map(|(entity, _ability)| entity)
+        .collect()
+}
+
+/// So do the status effects they were under.
+fn get_effects_on(world: &World, targets: &[Entity]) -> Vec<Entity> {
+    let effects = world.read_storage::<StatusEffect>();
+    let entities = world.entities();
+    (&entities, &effects)
+        .join()
+        .filter(|(_entity, effect)| targets.contains(&effect.target))
+        .map(|(entity, _effect)| entity)
+        .collect()
+}
+
 fn get_dead_entities(world: &World) -> Vec<Entity> {
     let mut dead: Vec<Entity> = Vec::new();
     let combat_stats = world.read_storage::<CombatStats>();
@@ -0,0 +1,45 @@
+use crate::map::RememberedGlyph;
+use crate::{Map, Monster, Npc, Player, Position, Renderable};
+use rltk::Point;
+use specs::prelude::*;
+
+/// Keeps the map's memory of what lies on each tile up to date. Whatever the player can make out
+/// replaces the old memory; things that walk around on their own aren't remembered, since they
+/// won't stay put.
+pub struct FogOfWar {}
+
+impl<'a> System<'a> for FogOfWar {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, Point>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Npc>,
+        ReadStorage<'a, Player>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, player_pos, positions, renderables, monsters, npcs, players) = data;
+
+        for idx in 0..map.tiles.len() {
+            if map.can_make_out(idx, *player_pos) {
+                map.remembered[idx] = None;
+            }
+        }
+
+        let mut seen = (&positions, &renderables, !&monsters, !&npcs, !&players)
+            .join()
+            .map(|(pos, render, _monster, _npc, _player)| (pos, render))
+            .filter(|(pos, _render)| map.can_make_out(map.xy_idx(pos.x, pos.y), *player_pos))
+            .collect::<Vec<_>>();
+        seen.sort_by(|a, b| a.1.index.cmp(&b.1.index));
+        for (pos, render) in seen {
+            let idx = map.xy_idx(pos.x, pos.y);
+            map.remembered[idx] = Some(RememberedGlyph {
+                glyph: render.glyph,
+                fg: render.fg,
+            });
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{DamageType, GameLog, HungerClock, HungerState, RunState, SufferDamage};
+use specs::prelude::*;
+
+/// How many turns each state lasts before hunger sets in further. Eating resets the clock to
+/// well fed.
+pub const WELL_FED_TURNS: i32 = 200;
+const NORMAL_TURNS: i32 = 300;
+const HUNGRY_TURNS: i32 = 200;
+/// What starving costs each turn.
+const STARVATION_DAMAGE: i32 = 1;
+/// How often a starving player is reminded of it.
+const HUNGER_PANG_TURNS: i32 = 10;
+
+/// Makes everyone with a hunger clock a little hungrier each turn the player takes: well fed,
+/// then normal, then hungry and finally starving, which hurts every turn until they eat.
+pub struct Hunger {}
+
+impl<'a> System<'a> for Hunger {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, HungerClock>,
+        WriteStorage<'a, SufferDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, run_state, player_entity, mut gamelog, mut hunger_clocks, mut suffer_damage) =
+            data;
+
+        if *run_state != RunState::PlayerTurn {
+            return;
+        }
+
+        for (entity, clock) in (&entities, &mut hunger_clocks).join() {
+            clock.turns -= 1;
+            if clock.turns > 0 {
+                continue;
+            }
+
+            let message = match clock.state {
+                HungerState::WellFed => {
+                    clock.state = HungerState::Normal;
+                    clock.turns = NORMAL_TURNS;
+                    Some("You are no longer well fed.")
+                }
+                HungerState::Normal => {
+                    clock.state = HungerState::Hungry;
+                    clock.turns = HUNGRY_TURNS;
+                    Some("You are hungry.")
+                }
+                HungerState::Hungry => {
+                    clock.state = HungerState::Starving;
+                    clock.turns = 0;
+                    Some("You are starving!")
+                }
+                // The clock keeps running below zero, counting the turns spent starving
+                HungerState::Starving => {
+                    SufferDamage::new_damage(
+                        &mut suffer_damage,
+                        entity,
+                        STARVATION_DAMAGE,
+                        DamageType::Physical,
+                        false,
+                    );
+                    if clock.turns % HUNGER_PANG_TURNS == 0 {
+                        Some("Your hunger pangs are getting painful!")
+                    } else {
+                        None
+                    }
+                }
+            };
+            if let (Some(message), true) = (message, entity == *player_entity) {
+                gamelog.entries.push(message.to_string());
+            }
+        }
+    }
+}
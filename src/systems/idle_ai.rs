@@ -0,0 +1,262 @@
+use super::{status_effects, terrain_effects};
+use crate::dijkstra::DijkstraCache;
+use crate::{
+    AiState, Corpse, Heavy, IdleBehaviour, InBackpack, Item, Map, Monster, PackMember,
+    PicksUpItems, Position, RunState, StatusEffect, TileType, Viewshed, WantsToPickupItem,
+};
+use rltk::{BaseMap, Point, RandomNumberGenerator};
+use specs::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// How far from home a wanderer strays on levels without rooms.
+const WANDER_RADIUS: f32 = 4.0;
+/// How many rooms besides its own a patrol takes in.
+const PATROL_ROOMS: usize = 2;
+/// Pack members drift back towards their leader once they are further away than this.
+const PACK_FOLLOW_DISTANCE: f32 = 2.5;
+
+/// Keeps monsters that haven't noticed any enemy busy: those that pick up items go for any they
+/// can see, pack members stay near their leader, and everyone else wanders, patrols or guards as
+/// their `IdleBehaviour` says. Nobody carrying heavy gear steps into deep water.
+pub struct IdleAI {}
+
+impl<'a> System<'a> for IdleAI {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, DijkstraCache>,
+        Entities<'a>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, AiState>,
+        ReadStorage<'a, PackMember>,
+        ReadStorage<'a, StatusEffect>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, Corpse>,
+        ReadStorage<'a, PicksUpItems>,
+        WriteStorage<'a, WantsToPickupItem>,
+        WriteStorage<'a, IdleBehaviour>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Heavy>,
+        ReadStorage<'a, InBackpack>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            run_state,
+            mut rng,
+            mut dijkstra,
+            entities,
+            monsters,
+            ai_states,
+            pack_members,
+            effects,
+            items,
+            corpses,
+            picks_up_items,
+            mut wants_pickup,
+            mut idle_behaviours,
+            mut positions,
+            mut viewsheds,
+            heavy,
+            backpacks,
+        ) = data;
+
+        if *run_state != RunState::MonsterTurn {
+            return;
+        }
+
+        let too_heavy_to_swim = terrain_effects::too_heavy_to_swim(&heavy, &backpacks);
+        let leaders: HashMap<Entity, Point> = (&entities, &positions)
+            .join()
+            .map(|(entity, pos)| (entity, Point::new(pos.x, pos.y)))
+            .collect();
+        // Corpses are items too, but no monster wants one
+        let floor_items: Vec<(Entity, usize)> = (&entities, &items, &positions, !&corpses)
+            .join()
+            .map(|(item, _item, pos, _corpse)| (item, map.xy_idx(pos.x, pos.y)))
+            .filter(|(_item, idx)| !map.tiles[*idx].is_hazardous())
+            .collect();
+        let mut claimed_items: HashSet<Entity> = HashSet::new();
+        let afflictions = status_effects::afflictions(&effects);
+
+        for (entity, _monster, state, behaviour, pos, viewshed) in (
+            &entities,
+            &monsters,
+            &ai_states,
+            &mut idle_behaviours,
+            &mut positions,
+            &mut viewsheds,
+        )
+            .join()
+        {
+            if *state != AiState::Idle || !status_effects::monster_can_act(&afflictions, entity) {
+                continue;
+            }
+
+            let here = Point::new(pos.x, pos.y);
+            let idx = map.xy_idx(pos.x, pos.y);
+            let leader_pos = pack_members
+                .get(entity)
+                .and_then(|member| leaders.get(&member.leader));
+            let wanted_item = if picks_up_items.get(entity).is_some() {
+                nearest_item(&map, &floor_items, &claimed_items, here, viewshed)
+            } else {
+                None
+            };
+            if let Some((item, item_idx)) = wanted_item {
+                if item_idx == idx {
+                    claimed_items.insert(item);
+                    wants_pickup
+                        .insert(
+                            entity,
+                            WantsToPickupItem {
+                                collected_by: entity,
+                                item,
+                            },
+                        )
+                        .expect("Unable to insert want to pickup");
+                    continue;
+                }
+            }
+
+            let step = if let Some((_item, item_idx)) = wanted_item {
+                dijkstra.towards(&map, &[item_idx]).downhill_step(&map, idx)
+            } else {
+                match leader_pos {
+                    Some(leader_pos)
+                        if rltk::DistanceAlg::Pythagoras.distance2d(here, *leader_pos)
+                            > PACK_FOLLOW_DISTANCE =>
+                    {
+                        let leader_idx = map.xy_idx(leader_pos.x, leader_pos.y);
+                        dijkstra
+                            .towards(&map, &[leader_idx])
+                            .downhill_step(&map, idx)
+                    }
+                    Some(_leader_pos) => None,
+                    None => idle_step(&map, &mut dijkstra, &mut rng, behaviour, idx),
+                }
+            };
+
+            let step = step.filter(|step| {
+                map.tiles[*step] != TileType::DeepWater || !too_heavy_to_swim.contains(&entity)
+            });
+            let wading = map.tiles[idx] == TileType::ShallowWater && rng.roll_dice(1, 2) == 1;
+            if let (Some(step), false) = (step, wading) {
+                map.blocked_tiles[idx] = false;
+
+                let new_pos = map.pos_from_idx(step);
+                pos.x = new_pos.x;
+                pos.y = new_pos.y;
+                map.blocked_tiles[step] = true;
+
+                viewshed.dirty = true;
+            }
+        }
+    }
+}
+
+/// The closest item in sight that nobody has grabbed yet, with the tile it's lying on.
+fn nearest_item(
+    map: &Map,
+    floor_items: &[(Entity, usize)],
+    claimed_items: &HashSet<Entity>,
+    here: Point,
+    viewshed: &Viewshed,
+) -> Option<(Entity, usize)> {
+    floor_items
+        .iter()
+        .filter(|(item, _idx)| !claimed_items.contains(item))
+        .map(|(item, idx)| (*item, *idx, map.pos_from_idx(*idx)))
+        .map(|(item, idx, pos)| (item, idx, Point::new(pos.x, pos.y)))
+        .filter(|(_item, _idx, pos)| viewshed.visible_tiles.contains(pos))
+        .min_by(|(_a, _a_idx, a), (_b, _b_idx, b)| {
+            let a = rltk::DistanceAlg::Pythagoras.distance2d(here, *a);
+            let b = rltk::DistanceAlg::Pythagoras.distance2d(here, *b);
+            a.partial_cmp(&b).unwrap()
+        })
+        .map(|(item, idx, _pos)| (item, idx))
+}
+
+/// Where an idle monster goes next, going by its idle behaviour.
+fn idle_step(
+    map: &Map,
+    dijkstra: &mut DijkstraCache,
+    rng: &mut RandomNumberGenerator,
+    behaviour: &mut IdleBehaviour,
+    idx: usize,
+) -> Option<usize> {
+    match behaviour {
+        IdleBehaviour::Wander { x, y } => {
+            // Wanderers spend most of their time standing about
+            if rng.roll_dice(1, 3) > 1 {
+                return None;
+            }
+            let home = Point::new(*x, *y);
+            let room = map.rooms.iter().find(|room| room.contains(home.x, home.y));
+            let exits: Vec<usize> = map
+                .get_available_exits(idx)
+                .iter()
+                .map(|(exit, _cost)| *exit)
+                .filter(|exit| {
+                    let pos = map.pos_from_idx(*exit);
+                    let near_home = match room {
+                        Some(room) => room.contains(pos.x, pos.y),
+                        None => {
+                            let pos = Point::new(pos.x, pos.y);
+                            rltk::DistanceAlg::Pythagoras.distance2d(home, pos) <= WANDER_RADIUS
+                        }
+                    };
+                    near_home && !map.tiles[*exit].is_hazardous()
+                })
+                .collect();
+            if exits.is_empty() {
+                None
+            } else {
+                Some(exits[rng.range(0, exits.len() as i32) as usize])
+            }
+        }
+        IdleBehaviour::Patrol { waypoints, next } => {
+            if waypoints.is_empty() {
+                *waypoints = plan_patrol(map, rng, idx);
+                *next = 0;
+            }
+            let waypoint = waypoints[*next];
+            let waypoint_idx = map.xy_idx(waypoint.0, waypoint.1);
+            let step = dijkstra
+                .towards(map, &[waypoint_idx])
+                .downhill_step(map, idx);
+            if step.is_none() {
+                // Arrived, or the way is blocked for now: on to the next waypoint
+                *next = (*next + 1) % waypoints.len();
+            }
+            step
+        }
+        IdleBehaviour::Guard { x, y } => {
+            let post = map.xy_idx(*x, *y);
+            if post == idx {
+                None
+            } else {
+                dijkstra.towards(map, &[post]).downhill_step(map, idx)
+            }
+        }
+    }
+}
+
+/// A patrol route: from where the monster is through the centres of a few other rooms. Levels
+/// without rooms leave it standing where it is.
+fn plan_patrol(map: &Map, rng: &mut RandomNumberGenerator, idx: usize) -> Vec<(i32, i32)> {
+    let start = map.pos_from_idx(idx);
+    let mut waypoints = vec![(start.x, start.y)];
+    let mut rooms: Vec<usize> = (0..map.rooms.len()).collect();
+    while waypoints.len() <= PATROL_ROOMS && !rooms.is_empty() {
+        let room = rooms.remove(rng.range(0, rooms.len() as i32) as usize);
+        let center = map.rooms[room].center();
+        if !map.rooms[room].contains(start.x, start.y) {
+            waypoints.push((center.x, center.y));
+        }
+    }
+    waypoints
+}
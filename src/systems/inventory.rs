@@ -1,4 +1,5 @@
-use crate::{GameLog, InBackpack, Name, Position, WantsToPickupItem};
+use crate::{GameLog, InBackpack, Map, Name, Position, WantsToPickupItem};
+use rltk::Point;
 use specs::prelude::*;
 
 pub struct Inventory;
@@ -6,6 +7,8 @@ pub struct Inventory;
 impl<'a> System<'a> for Inventory {
     type SystemData = (
         ReadExpect<'a, Entity>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Point>,
         ReadStorage<'a, Name>,
         WriteExpect<'a, GameLog>,
         WriteStorage<'a, WantsToPickupItem>,
@@ -14,11 +17,23 @@ impl<'a> System<'a> for Inventory {
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, names, mut gamelog, mut wants_pickup, mut positions, mut backpack) =
-            data;
+        let (
+            player_entity,
+            map,
+            player_pos,
+            names,
+            mut gamelog,
+            mut wants_pickup,
+            mut positions,
+            mut backpack,
+        ) = data;
 
         for pickup in wants_pickup.join() {
-            positions.remove(pickup.item);
+            // Someone else got there first
+            let item_pos = match positions.remove(pickup.item) {
+                Some(item_pos) => item_pos,
+                None => continue,
+            };
             backpack
                 .insert(
                     pickup.item,
@@ -28,11 +43,18 @@ impl<'a> System<'a> for Inventory {
                 )
                 .expect("Unable to insert Backpack entry");
 
+            let item_name = &names.get(pickup.item).unwrap().name;
             if pickup.collected_by == *player_entity {
-                gamelog.entries.push(format!(
-                    "You pick up the {}.",
-                    names.get(pickup.item).unwrap().name
-                ));
+                gamelog
+                    .entries
+                    .push(format!("You pick up the {}.", item_name));
+            } else if map.can_make_out(map.xy_idx(item_pos.x, item_pos.y), *player_pos) {
+                if let Some(collector) = names.get(pickup.collected_by) {
+                    gamelog.entries.push(format!(
+                        "The {} picks up the {}.",
+                        collector.name, item_name
+                    ));
+                }
             }
         }
 
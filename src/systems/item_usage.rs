@@ -1,15 +1,21 @@
+use super::{attribute_bonus, WELL_FED_TURNS};
+use crate::noise::{Noises, EXPLOSION_VOLUME};
 use crate::{
-    AreaOfEffect, CombatStats, Confusion, Consumable, GameLog, InflictsDamage, Map, Name,
-    ProvidesHealing, SufferDamage, WantsToUseItem,
+    geometry, AreaOfEffect, Attributes, Charges, Charm, CombatStats, Consumable, DestroysTerrain,
+    Digger, DigsTerrain, Faction, GameLog, HungerClock, HungerState, InflictsDamage,
+    InflictsStatus, Lifetime, LightSource, Map, Name, Position, ProvidesFood, ProvidesHealing,
+    Ranged, RunStats, SerializeMe, StatusEffect, SufferDamage, TileType, Viewshed, WantsToUseItem,
 };
+use rltk::{Point, RGB};
 use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
 
 pub struct ItemUsage {}
 
 impl<'a> System<'a> for ItemUsage {
     type SystemData = (
         ReadExpect<'a, Entity>,
-        ReadExpect<'a, Map>,
+        WriteExpect<'a, Map>,
         WriteExpect<'a, GameLog>,
         Entities<'a>,
         WriteStorage<'a, WantsToUseItem>,
@@ -20,13 +26,27 @@ impl<'a> System<'a> for ItemUsage {
         ReadStorage<'a, AreaOfEffect>,
         WriteStorage<'a, CombatStats>,
         WriteStorage<'a, SufferDamage>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, InflictsStatus>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Ranged>,
+        // Some storages are paired up, as system data only takes 26 items at the top level
+        (ReadStorage<'a, DigsTerrain>, ReadStorage<'a, Digger>),
+        ReadStorage<'a, DestroysTerrain>,
+        WriteStorage<'a, Charges>,
+        WriteStorage<'a, Viewshed>,
+        Read<'a, LazyUpdate>,
+        ReadStorage<'a, Charm>,
+        WriteStorage<'a, Faction>,
+        WriteExpect<'a, Noises>,
+        WriteStorage<'a, RunStats>,
+        ReadStorage<'a, Attributes>,
+        (ReadStorage<'a, ProvidesFood>, WriteStorage<'a, HungerClock>),
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
             player_entity,
-            map,
+            mut map,
             mut gamelog,
             entities,
             mut wants_use,
@@ -37,11 +57,34 @@ impl<'a> System<'a> for ItemUsage {
             area_of_effect,
             mut combat_stats,
             mut suffer_damage,
-            mut confusion,
+            inflicts_status,
+            positions,
+            ranged,
+            (digs_terrain, diggers),
+            destroys_terrain,
+            mut charges,
+            mut viewsheds,
+            lazy,
+            charms,
+            mut factions,
+            mut noises,
+            mut run_stats,
+            attributes,
+            (food, mut hunger_clocks),
         ) = data;
 
         for (entity, usage) in (&entities, &wants_use).join() {
-            let targets = get_targets(&usage, *player_entity, &area_of_effect, &map);
+            let targets = get_targets(&usage, entity, &area_of_effect, &combat_stats, &map);
+
+            announce_ability(
+                entity,
+                &usage,
+                &player_entity,
+                &names,
+                &positions,
+                &viewsheds,
+                &mut gamelog,
+            );
 
             apply_healing(
                 entity,
@@ -54,19 +97,33 @@ impl<'a> System<'a> for ItemUsage {
                 &mut gamelog,
             );
 
-            apply_confusion(
+            apply_food(
+                entity,
+                &food,
+                &usage,
+                &player_entity,
+                &names,
+                &targets,
+                &mut hunger_clocks,
+                &mut gamelog,
+            );
+
+            apply_status(
                 entity,
+                &entities,
+                &lazy,
+                &inflicts_status,
                 &usage,
                 &player_entity,
                 &names,
                 &targets,
-                &mut confusion,
                 &mut gamelog,
             );
 
             apply_damage(
                 entity,
                 &damage,
+                &attributes,
                 &usage,
                 &player_entity,
                 &names,
@@ -75,13 +132,96 @@ impl<'a> System<'a> for ItemUsage {
                 &mut gamelog,
             );
 
+            explode(
+                entity,
+                &entities,
+                &lazy,
+                &damage,
+                &area_of_effect,
+                &usage,
+                &map,
+                &mut noises,
+            );
+
+            apply_charm(
+                entity,
+                &charms,
+                &usage,
+                &player_entity,
+                &names,
+                &targets,
+                &mut factions,
+                &mut gamelog,
+            );
+
+            let mut changed_tiles = apply_digging(
+                entity,
+                &digs_terrain,
+                &ranged,
+                &positions,
+                &usage,
+                &player_entity,
+                &mut map,
+                &mut gamelog,
+            );
+            changed_tiles.extend(apply_terrain_destruction(
+                &destroys_terrain,
+                &area_of_effect,
+                &usage,
+                &mut map,
+            ));
+            if !changed_tiles.is_empty() {
+                map.dirty_viewsheds_seeing(&mut viewsheds, &changed_tiles);
+            }
+
+            if diggers.get(usage.item).is_some() && entity == *player_entity {
+                gamelog
+                    .entries
+                    .push("Walk into a wall to dig through it.".to_string());
+            }
+
+            if let Some(run_stats) = run_stats.get_mut(entity) {
+                run_stats.items_used += 1;
+            }
+
             clear_consumables(&entities, &consumables, usage.item);
+            use_charge(&entities, &mut charges, usage.item);
         }
 
         wants_use.clear();
     }
 }
 
+/// Tells the player when they see someone else use something.
+fn announce_ability(
+    entity: Entity,
+    usage: &WantsToUseItem,
+    player_entity: &Entity,
+    names: &ReadStorage<Name>,
+    positions: &ReadStorage<Position>,
+    viewsheds: &WriteStorage<Viewshed>,
+    gamelog: &mut GameLog,
+) {
+    if entity == *player_entity {
+        return;
+    }
+    let (user_pos, player_viewshed) = match (positions.get(entity), viewsheds.get(*player_entity)) {
+        (Some(user_pos), Some(player_viewshed)) => (user_pos, player_viewshed),
+        _ => return,
+    };
+    if !player_viewshed
+        .visible_tiles
+        .contains(&Point::new(user_pos.x, user_pos.y))
+    {
+        return;
+    }
+    if let (Some(user), Some(item)) = (names.get(entity), names.get(usage.item)) {
+        gamelog
+            .entries
+            .push(format!("The {} uses {}.", user.name, item.name));
+    }
+}
+
 fn clear_consumables(entities: &Entities, consumables: &ReadStorage<Consumable>, item: Entity) {
     let consumable = consumables.get(item);
     if consumable.is_some() {
@@ -89,23 +229,73 @@ fn clear_consumables(entities: &Entities, consumables: &ReadStorage<Consumable>,
     }
 }
 
+fn use_charge(entities: &Entities, charges: &mut WriteStorage<Charges>, item: Entity) {
+    if let Some(charges) = charges.get_mut(item) {
+        charges.remaining -= 1;
+        if charges.remaining < 1 {
+            entities.delete(item).expect("Delete failed");
+        }
+    }
+}
+
+/// Damaging blasts light up their surroundings for a moment, and can be heard from far off.
+fn explode(
+    entity: Entity,
+    entities: &Entities,
+    lazy: &LazyUpdate,
+    damage: &ReadStorage<InflictsDamage>,
+    area_of_effect: &ReadStorage<AreaOfEffect>,
+    usage: &WantsToUseItem,
+    map: &Map,
+    noises: &mut Noises,
+) {
+    let (target, area_effect) = match (usage.target, area_of_effect.get(usage.item)) {
+        (Some(target), Some(area_effect)) => (target, area_effect),
+        _ => return,
+    };
+    if damage.get(usage.item).is_none() {
+        return;
+    }
+
+    lazy.create_entity(entities)
+        .with(Position {
+            x: target.x,
+            y: target.y,
+        })
+        .with(LightSource {
+            colour: RGB::from_f32(0.969, 0.463, 0.133),
+            range: area_effect.radius + 2,
+        })
+        .with(Lifetime { turns: 1 })
+        .build();
+    noises.make(
+        map.xy_idx(target.x, target.y),
+        EXPLOSION_VOLUME,
+        Some(entity),
+    );
+}
+
+/// Who an item affects: the creatures at the target, or the user itself when it isn't aimed.
+/// Items and corpses lying about aren't hit.
 fn get_targets(
     usage: &WantsToUseItem,
-    player_entity: Entity,
+    user: Entity,
     area_of_effect: &ReadStorage<AreaOfEffect>,
+    combat_stats: &WriteStorage<CombatStats>,
     map: &Map,
 ) -> Vec<Entity> {
     let mut targets: Vec<Entity> = Vec::new();
     match usage.target {
         None => {
-            targets.push(player_entity);
+            targets.push(user);
         }
         Some(target) => {
             let area_effect = area_of_effect.get(usage.item);
             match area_effect {
                 None => {
                     let idx = map.xy_idx(target.x, target.y);
-                    for mob in map.tile_content[idx].iter() {
+                    let mobs = map.tile_content[idx].iter();
+                    for mob in mobs.filter(|mob| combat_stats.get(**mob).is_some()) {
                         targets.push(*mob);
                         break; // Non AoE should get the First One
                     }
@@ -115,7 +305,8 @@ fn get_targets(
                     target_tiles.retain(|p| map.is_inside_map(p.x, p.y));
                     for tile_idx in target_tiles.iter() {
                         let idx = map.xy_idx(tile_idx.x, tile_idx.y);
-                        for mob in map.tile_content[idx].iter() {
+                        let mobs = map.tile_content[idx].iter();
+                        for mob in mobs.filter(|mob| combat_stats.get(**mob).is_some()) {
                             targets.push(*mob);
                         }
                     }
@@ -154,9 +345,39 @@ fn apply_healing(
     }
 }
 
+/// Eating leaves the eater well fed, however hungry it was.
+fn apply_food(
+    entity: Entity,
+    food: &ReadStorage<ProvidesFood>,
+    usage: &WantsToUseItem,
+    player_entity: &Entity,
+    names: &ReadStorage<Name>,
+    targets: &[Entity],
+    hunger_clocks: &mut WriteStorage<HungerClock>,
+    gamelog: &mut GameLog,
+) {
+    if food.get(usage.item).is_none() {
+        return;
+    }
+    for target in targets.iter() {
+        if let Some(clock) = hunger_clocks.get_mut(*target) {
+            clock.state = HungerState::WellFed;
+            clock.turns = WELL_FED_TURNS;
+            if entity == *player_entity {
+                gamelog.entries.push(format!(
+                    "You eat the {}.",
+                    names.get(usage.item).unwrap().name
+                ));
+            }
+        }
+    }
+}
+
+/// Scrolls and spells do more damage in clever hands: the user's intelligence bonus is added.
 fn apply_damage(
     entity: Entity,
     damage: &ReadStorage<InflictsDamage>,
+    attributes: &ReadStorage<Attributes>,
     usage: &WantsToUseItem,
     player_entity: &Entity,
     names: &ReadStorage<Name>,
@@ -166,57 +387,191 @@ fn apply_damage(
 ) {
     let damage_item = damage.get(usage.item);
     if let Some(damage_item) = damage_item {
+        let intelligence = attributes
+            .get(entity)
+            .map_or(0, |attributes| attribute_bonus(attributes.intelligence));
+        let amount = i32::max(1, damage_item.damage + intelligence);
         for mob in targets.iter() {
-            suffer_damage
-                .insert(
-                    *mob,
-                    SufferDamage {
-                        amount: damage_item.damage,
-                    },
-                )
-                .expect("Unable to insert damage to mob");
+            SufferDamage::new_damage(
+                suffer_damage,
+                *mob,
+                amount,
+                damage_item.damage_type,
+                entity == *player_entity,
+            );
             if entity == *player_entity {
                 let mob_name = names.get(*mob).unwrap();
                 let item_name = names.get(usage.item).unwrap();
                 gamelog.entries.push(format!(
                     "You used the {} on {}, inflicting {} damage.",
-                    item_name.name, mob_name.name, damage_item.damage,
+                    item_name.name, mob_name.name, amount,
+                ));
+            } else if *mob == *player_entity {
+                gamelog.entries.push(format!(
+                    "The {} hits you, for {} hp.",
+                    names.get(usage.item).unwrap().name,
+                    amount,
                 ));
             }
         }
     }
 }
 
-fn apply_confusion(
+/// Puts the targets under the item's status effect, as new effect entities.
+fn apply_status(
     entity: Entity,
+    entities: &Entities,
+    lazy: &LazyUpdate,
+    inflicts_status: &ReadStorage<InflictsStatus>,
     usage: &WantsToUseItem,
     player_entity: &Entity,
     names: &ReadStorage<Name>,
-    targets: &Vec<Entity>,
-    confusion: &mut WriteStorage<Confusion>,
+    targets: &[Entity],
     gamelog: &mut GameLog,
 ) {
-    let mut confusions_to_add = Vec::new();
-    {
-        let item = confusion.get(usage.item);
-        if let Some(item) = item {
-            for mob in targets.iter() {
-                confusions_to_add.push((*mob, Confusion { turns: item.turns }));
-                if entity == *player_entity {
-                    let mob_name = names.get(*mob).unwrap();
-                    let item_name = names.get(usage.item).unwrap();
+    let status = match inflicts_status.get(usage.item) {
+        Some(status) => status,
+        None => return,
+    };
+    let state = status.kind.name().to_lowercase();
+    for mob in targets.iter() {
+        lazy.create_entity(entities)
+            .with(StatusEffect {
+                target: *mob,
+                kind: status.kind,
+                turns: status.turns,
+                potency: status.potency,
+            })
+            .marked::<SimpleMarker<SerializeMe>>()
+            .build();
+
+        if *mob == *player_entity {
+            gamelog.entries.push(format!(
+                "The {} leaves you {} for {} turns.",
+                names.get(usage.item).unwrap().name,
+                state,
+                status.turns,
+            ));
+        } else if entity == *player_entity {
+            gamelog.entries.push(format!(
+                "You used the {} on {}, leaving it {} for {} turns.",
+                names.get(usage.item).unwrap().name,
+                names.get(*mob).unwrap().name,
+                state,
+                status.turns,
+            ));
+        }
+    }
+}
+
+/// Targets with a faction switch to the user's side.
+fn apply_charm(
+    entity: Entity,
+    charms: &ReadStorage<Charm>,
+    usage: &WantsToUseItem,
+    player_entity: &Entity,
+    names: &ReadStorage<Name>,
+    targets: &[Entity],
+    factions: &mut WriteStorage<Faction>,
+    gamelog: &mut GameLog,
+) {
+    if charms.get(usage.item).is_none() {
+        return;
+    }
+    let user_faction = match factions.get(entity) {
+        Some(faction) => faction.clone(),
+        None => return,
+    };
+
+    for target in targets.iter() {
+        if *target == entity {
+            continue;
+        }
+        if let Some(faction) = factions.get_mut(*target) {
+            faction.name = user_faction.name.clone();
+            if entity == *player_entity {
+                if let Some(name) = names.get(*target) {
                     gamelog.entries.push(format!(
-                        "You used the {} on {}, causing {} turns of confusion.",
-                        item_name.name, mob_name.name, item.turns,
+                        "The {} is charmed, and joins your side.",
+                        name.name
                     ));
                 }
             }
         }
     }
+}
 
-    for (mob, new_confusion) in confusions_to_add {
-        confusion
-            .insert(mob, new_confusion)
-            .expect("Unable to apply confusion to mob");
+/// Tunnels through every wall on a straight line from the user towards the target, out to the
+/// item's full range. Returns the tiles that were dug out.
+fn apply_digging(
+    entity: Entity,
+    digs_terrain: &ReadStorage<DigsTerrain>,
+    ranged: &ReadStorage<Ranged>,
+    positions: &ReadStorage<Position>,
+    usage: &WantsToUseItem,
+    player_entity: &Entity,
+    map: &mut Map,
+    gamelog: &mut GameLog,
+) -> Vec<usize> {
+    let mut dug = Vec::new();
+    if digs_terrain.get(usage.item).is_none() {
+        return dug;
     }
+    let (target, origin) = match (usage.target, positions.get(entity)) {
+        (Some(target), Some(origin)) => (target, Point::new(origin.x, origin.y)),
+        _ => return dug,
+    };
+
+    let range = ranged.get(usage.item).map_or(1, |ranged| ranged.range);
+    let end = geometry::extend_to(origin, target, range);
+    for point in geometry::line(origin, end).iter().skip(1) {
+        if point.x < 0 || point.x >= map.width || point.y < 0 || point.y >= map.height {
+            break;
+        }
+        let idx = map.xy_idx(point.x, point.y);
+        if map.is_diggable(idx) {
+            map.change_tile(idx, TileType::Floor);
+            dug.push(idx);
+        }
+    }
+
+    if entity == *player_entity {
+        let message = if dug.is_empty() {
+            "The wand hums, but there is nothing to dig.".to_string()
+        } else {
+            format!("You bore a tunnel through {} tiles of rock.", dug.len())
+        };
+        gamelog.entries.push(message);
+    }
+
+    dug
+}
+
+/// Blows apart doors and barricades caught in an area effect. Returns the tiles destroyed.
+fn apply_terrain_destruction(
+    destroys_terrain: &ReadStorage<DestroysTerrain>,
+    area_of_effect: &ReadStorage<AreaOfEffect>,
+    usage: &WantsToUseItem,
+    map: &mut Map,
+) -> Vec<usize> {
+    let mut destroyed = Vec::new();
+    if destroys_terrain.get(usage.item).is_none() {
+        return destroyed;
+    }
+    let (target, area_effect) = match (usage.target, area_of_effect.get(usage.item)) {
+        (Some(target), Some(area_effect)) => (target, area_effect),
+        _ => return destroyed,
+    };
+
+    let mut target_tiles = rltk::field_of_view(target, area_effect.radius, &*map);
+    target_tiles.retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height);
+    for tile in target_tiles.iter() {
+        let idx = map.xy_idx(tile.x, tile.y);
+        if map.tiles[idx].is_destructible() {
+            map.change_tile(idx, TileType::Floor);
+            destroyed.push(idx);
+        }
+    }
+
+    destroyed
 }
@@ -0,0 +1,102 @@
+use crate::map_themes::Themes;
+use crate::{InBackpack, Lifetime, LightSource, Map, Position, RunState, TileType, Viewshed};
+use rltk::{field_of_view, Point, RGB};
+use specs::prelude::*;
+
+/// Light given off by a lava tile, onto that tile only.
+const LAVA_GLOW: RGB = RGB {
+    r: 0.97,
+    g: 0.46,
+    b: 0.13,
+};
+
+/// Works out the light map: the level's ambient light plus everything giving off light, with
+/// carried lights shining from whoever carries them. Short-lived lights burn out at the end of
+/// each round.
+pub struct Lighting {}
+
+impl<'a> System<'a> for Lighting {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, Themes>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Entity>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, LightSource>,
+        ReadStorage<'a, InBackpack>,
+        WriteStorage<'a, Lifetime>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            themes,
+            run_state,
+            player_entity,
+            entities,
+            positions,
+            light_sources,
+            backpacks,
+            mut lifetimes,
+            mut viewsheds,
+        ) = data;
+
+        let ambient = themes.for_depth(map.depth).ambient_light;
+        let mut light = vec![RGB::from_f32(ambient, ambient, ambient); map.tiles.len()];
+        for (idx, tile) in map.tiles.iter().enumerate() {
+            if *tile == TileType::Lava {
+                light[idx] = add_light(light[idx], LAVA_GLOW, 1.0);
+            }
+        }
+
+        let mut sources: Vec<(Point, &LightSource)> = Vec::new();
+        for (pos, source) in (&positions, &light_sources).join() {
+            sources.push((Point::new(pos.x, pos.y), source));
+        }
+        for (pack, source) in (&backpacks, &light_sources).join() {
+            if let Some(pos) = positions.get(pack.owner) {
+                sources.push((Point::new(pos.x, pos.y), source));
+            }
+        }
+
+        for (origin, source) in sources {
+            let lit_tiles = field_of_view(origin, source.range, &*map);
+            for tile in lit_tiles.iter() {
+                if tile.x < 0 || tile.x >= map.width || tile.y < 0 || tile.y >= map.height {
+                    continue;
+                }
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(origin, *tile);
+                let intensity = 1.0 - distance / (source.range as f32 + 1.0);
+                let idx = map.xy_idx(tile.x, tile.y);
+                light[idx] = add_light(light[idx], source.colour, intensity);
+            }
+        }
+
+        // What the player can make out depends on the light, so a change means looking again
+        if light != map.light {
+            map.light = light;
+            if let Some(viewshed) = viewsheds.get_mut(*player_entity) {
+                viewshed.dirty = true;
+            }
+        }
+
+        if *run_state == RunState::MonsterTurn {
+            for (entity, lifetime) in (&entities, &mut lifetimes).join() {
+                lifetime.turns -= 1;
+                if lifetime.turns < 1 {
+                    entities.delete(entity).expect("Unable to delete");
+                }
+            }
+        }
+    }
+}
+
+fn add_light(light: RGB, colour: RGB, intensity: f32) -> RGB {
+    RGB::from_f32(
+        f32::min(1.0, light.r + colour.r * intensity),
+        f32::min(1.0, light.g + colour.g * intensity),
+        f32::min(1.0, light.b + colour.b * intensity),
+    )
+}
@@ -1,22 +1,75 @@
-use crate::{CombatStats, GameLog, Name, SufferDamage, WantsToMelee};
+use super::status_effects;
+use crate::noise::{Noises, COMBAT_VOLUME};
+use crate::{
+    AiState, Attributes, Boss, CombatStats, DamageType, GameLog, Map, MeleeDamage, Name, Position,
+    StatusEffect, SufferDamage, Viewshed, WantsToMelee,
+};
+use rltk::{Point, RandomNumberGenerator};
 use specs::prelude::*;
 
+/// Attacks on monsters that haven't noticed their attacker hit this much harder.
+const SNEAK_ATTACK_MULTIPLIER: i32 = 2;
+/// The armour class of a creature with no defense and average quickness.
+const BASE_ARMOUR_CLASS: i32 = 10;
+/// Damage dealt by creatures without `MeleeDamage`: 1d4, their bare hands.
+const UNARMED: MeleeDamage = MeleeDamage { dice: 1, sides: 4 };
+
+/// The bonus or penalty an ability score gives, d20 style.
+pub fn attribute_bonus(score: i32) -> i32 {
+    (score - 10).div_euclid(2)
+}
+
+/// Resolves melee attacks: a d20 plus the attacker's might bonus and power has to reach the
+/// target's armour class. A natural 20 always hits and rolls the damage dice twice; a natural 1
+/// always misses. Targets that can't have seen their attacker coming, being asleep or idle with
+/// the attacker out of sight, or that can't do anything about it because they're magically asleep
+/// or stunned, are hit without a roll, harder. Bosses are never caught unaware.
 pub struct MeleeCombat {}
 
 impl<'a> System<'a> for MeleeCombat {
     type SystemData = (
         Entities<'a>,
         WriteExpect<'a, GameLog>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, Noises>,
+        WriteExpect<'a, RandomNumberGenerator>,
         WriteStorage<'a, WantsToMelee>,
         ReadStorage<'a, Name>,
         ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Attributes>,
+        ReadStorage<'a, MeleeDamage>,
         WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, AiState>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, StatusEffect>,
+        ReadStorage<'a, Boss>,
+        ReadStorage<'a, Viewshed>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut log, mut wants_melee, names, combat_stats, mut inflict_damage) = data;
+        let (
+            entities,
+            mut log,
+            map,
+            player_entity,
+            mut noises,
+            mut rng,
+            mut wants_melee,
+            names,
+            combat_stats,
+            attributes,
+            melee_damage,
+            mut inflict_damage,
+            ai_states,
+            positions,
+            effects,
+            bosses,
+            viewsheds,
+        ) = data;
+        let afflictions = status_effects::afflictions(&effects);
 
-        for (_entity, wants_melee, name, stats) in
+        for (entity, wants_melee, name, stats) in
             (&entities, &wants_melee, &names, &combat_stats).join()
         {
             if stats.hp > 0 {
@@ -24,21 +77,79 @@ impl<'a> System<'a> for MeleeCombat {
                 if target_stats.hp > 0 {
                     let target_name = names.get(wants_melee.target).unwrap();
 
-                    let damage = i32::max(0, stats.power - target_stats.defense);
+                    let might = attributes
+                        .get(entity)
+                        .map_or(0, |attributes| attribute_bonus(attributes.might));
+                    let quickness = attributes
+                        .get(wants_melee.target)
+                        .map_or(0, |attributes| attribute_bonus(attributes.quickness));
+                    let armour_class = BASE_ARMOUR_CLASS + target_stats.defense + quickness;
 
-                    if damage == 0 {
+                    let unaware = bosses.get(wants_melee.target).is_none()
+                        && (status_effects::is_helpless(&afflictions, wants_melee.target)
+                            || match ai_states.get(wants_melee.target) {
+                                Some(AiState::Asleep) => true,
+                                Some(AiState::Idle) => !in_sight(
+                                    viewsheds.get(wants_melee.target),
+                                    positions.get(entity),
+                                ),
+                                _ => false,
+                            });
+                    let natural_roll = rng.roll_dice(1, 20);
+                    let critical = natural_roll == 20;
+                    let hit = unaware
+                        || critical
+                        || (natural_roll > 1 && natural_roll + might + stats.power >= armour_class);
+
+                    if hit {
+                        let weapon = melee_damage.get(entity).unwrap_or(&UNARMED);
+                        let dice = if critical {
+                            weapon.dice * 2
+                        } else {
+                            weapon.dice
+                        };
+                        let damage = i32::max(1, rng.roll_dice(dice, weapon.sides) + might);
+                        let damage = if unaware {
+                            damage * SNEAK_ATTACK_MULTIPLIER
+                        } else {
+                            damage
+                        };
+
+                        if unaware {
+                            log.entries.push(format!(
+                                "{} catches {} unaware, for {} hp!",
+                                &name.name, &target_name.name, damage
+                            ));
+                        } else if critical {
+                            log.entries.push(format!(
+                                "{} lands a critical hit on {}, for {} hp!",
+                                &name.name, &target_name.name, damage
+                            ));
+                        } else {
+                            log.entries.push(format!(
+                                "{} hits {}, for {} hp.",
+                                &name.name, &target_name.name, damage
+                            ));
+                        }
+                        SufferDamage::new_damage(
+                            &mut inflict_damage,
+                            wants_melee.target,
+                            damage,
+                            DamageType::Physical,
+                            entity == *player_entity,
+                        );
+                    } else if natural_roll == 1 {
                         log.entries.push(format!(
-                            "{} is unable to hurt {}",
+                            "{} fumbles the attack on {}.",
                             &name.name, &target_name.name
                         ));
                     } else {
-                        log.entries.push(format!(
-                            "{} hits {}, for {} hp.",
-                            &name.name, &target_name.name, damage
-                        ));
-                        inflict_damage
-                            .insert(wants_melee.target, SufferDamage { amount: damage })
-                            .expect("Unable to do damage");
+                        log.entries
+                            .push(format!("{} misses {}.", &name.name, &target_name.name));
+                    }
+
+                    if let Some(pos) = positions.get(wants_melee.target) {
+                        noises.make(map.xy_idx(pos.x, pos.y), COMBAT_VOLUME, Some(entity));
                     }
                 }
             }
@@ -47,3 +158,11 @@ impl<'a> System<'a> for MeleeCombat {
         wants_melee.clear();
     }
 }
+
+/// Whether a creature looking out through `viewshed` can see whoever stands at `pos`.
+fn in_sight(viewshed: Option<&Viewshed>, pos: Option<&Position>) -> bool {
+    match (viewshed, pos) {
+        (Some(viewshed), Some(pos)) => viewshed.visible_tiles.contains(&Point::new(pos.x, pos.y)),
+        _ => false,
+    }
+}
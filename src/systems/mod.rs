@@ -1,9 +1,27 @@
 pub mod visibility;
 pub use visibility::*;
 
+pub mod lighting;
+pub use lighting::*;
+
+pub mod fog_of_war;
+pub use fog_of_war::*;
+
 pub mod monster_ai;
 pub use monster_ai::*;
 
+pub mod awareness;
+pub use awareness::*;
+
+pub mod morale;
+pub use morale::*;
+
+pub mod idle_ai;
+pub use idle_ai::*;
+
+pub mod npc_ai;
+pub use npc_ai::*;
+
 pub mod map_indexing;
 pub use map_indexing::*;
 
@@ -13,6 +31,18 @@ pub use melee_combat::*;
 pub mod damage;
 pub use damage::*;
 
+pub mod boss_phases;
+pub use boss_phases::*;
+
+pub mod terrain_effects;
+pub use terrain_effects::*;
+
+pub mod status_effects;
+pub use status_effects::*;
+
+pub mod hunger;
+pub use hunger::*;
+
 pub mod inventory;
 pub use inventory::*;
 
@@ -1,7 +1,30 @@
-use crate::{Confusion, Map, Monster, Name, Position, RunState, Viewshed, WantsToMelee};
-use rltk::{console, Algorithm2D, Point};
+use super::status_effects::{self, Afflictions};
+use super::terrain_effects;
+use super::visibility;
+use crate::dijkstra::{DijkstraCache, DijkstraMap};
+use crate::factions::{FactionTable, Reaction};
+use crate::{
+    geometry, Ability, AiState, AreaOfEffect, CombatStats, Faction, Heavy, InBackpack,
+    InflictsDamage, InflictsStatus, KeepsDistance, Map, Monster, Name, PackMember, Position,
+    ProvidesHealing, Ranged, RunState, StatusEffect, StatusKind, TileType, Viewshed, WantsToMelee,
+    WantsToUseItem,
+};
+use rltk::{console, BaseMap, Point, RandomNumberGenerator};
 use specs::prelude::*;
+use specs::storage::GenericReadStorage;
+use std::collections::{HashMap, HashSet};
 
+/// How many turns a monster keeps searching once it reaches the spot an enemy was last seen.
+const SEARCH_TURNS: i32 = 5;
+/// How far from that spot a searching monster will wander.
+const SEARCH_RADIUS: f32 = 3.0;
+/// How close pack mates have to be before the pack closes in on an enemy.
+const PACK_GATHER_RANGE: f32 = 4.0;
+/// Within this distance of its target a pack member stops waiting for the others.
+const PACK_ENGAGE_RANGE: f32 = 3.0;
+
+/// Gives every monster its turn: it goes after the creatures its faction is hostile to, or runs
+/// from them once its nerve breaks.
 pub struct MonsterAI {}
 
 impl<'a> System<'a> for MonsterAI {
@@ -9,92 +32,651 @@ impl<'a> System<'a> for MonsterAI {
         WriteExpect<'a, Map>,
         WriteStorage<'a, Viewshed>,
         ReadExpect<'a, RunState>,
-        ReadExpect<'a, Point>,
-        ReadExpect<'a, Entity>,
         Entities<'a>,
         ReadStorage<'a, Monster>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, WantsToMelee>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, StatusEffect>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, DijkstraCache>,
+        ReadExpect<'a, FactionTable>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, AiState>,
+        WriteStorage<'a, Ability>,
+        WriteStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, KeepsDistance>,
+        ReadStorage<'a, Ranged>,
+        ReadStorage<'a, ProvidesHealing>,
+        ReadStorage<'a, AreaOfEffect>,
+        ReadStorage<'a, PackMember>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, InflictsDamage>,
+        ReadStorage<'a, InflictsStatus>,
+        ReadStorage<'a, Heavy>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
-            mut map,
+            map,
             mut viewshed,
             run_state,
-            player_pos,
-            player_entity,
             entities,
             monster,
             name,
             mut position,
-            mut wants_to_melee,
-            mut confusion,
+            wants_to_melee,
+            effects,
+            rng,
+            dijkstra,
+            faction_table,
+            factions,
+            combat_stats,
+            ai_states,
+            mut abilities,
+            wants_to_use,
+            keeps_distance,
+            ranged,
+            healing,
+            area_of_effect,
+            pack_members,
+            backpacks,
+            damage,
+            inflicts_status,
+            heavy,
         ) = data;
 
         if *run_state != RunState::MonsterTurn {
             return;
         }
 
+        let mut known_abilities: HashMap<Entity, Vec<Entity>> = HashMap::new();
+        for (ability_entity, ability) in (&entities, &mut abilities).join() {
+            if ability.ready_in > 0 {
+                ability.ready_in -= 1;
+            }
+            known_abilities
+                .entry(ability.owner)
+                .or_insert_with(Vec::new)
+                .push(ability_entity);
+        }
+        // Carried items are used like abilities that are always ready, except potions, which are
+        // drunk rather than aimed
+        let mut potions: HashMap<Entity, Entity> = HashMap::new();
+        for (item, carried) in (&entities, &backpacks).join() {
+            if healing.get(item).is_some() && ranged.get(item).is_none() {
+                potions.insert(carried.owner, item);
+            } else {
+                known_abilities
+                    .entry(carried.owner)
+                    .or_insert_with(Vec::new)
+                    .push(item);
+            }
+        }
+
+        // Packs are keyed by their leader; monsters on their own make a pack of one
+        let mut packs: HashMap<Entity, Vec<(Entity, Point)>> = HashMap::new();
+        for (entity, _monster, pos) in (&entities, &monster, &position).join() {
+            let leader = pack_members
+                .get(entity)
+                .map_or(entity, |member| member.leader);
+            packs
+                .entry(leader)
+                .or_insert_with(Vec::new)
+                .push((entity, Point::new(pos.x, pos.y)));
+        }
+        let afflictions = status_effects::afflictions(&effects);
+
+        let mut turn = Turn {
+            map,
+            rng,
+            dijkstra,
+            faction_table,
+            factions,
+            combat_stats,
+            ai_states,
+            abilities,
+            wants_to_melee,
+            wants_to_use,
+            keeps_distance,
+            ranged,
+            healing,
+            area_of_effect,
+            pack_members,
+            damage,
+            inflicts_status,
+            afflictions,
+            known_abilities,
+            potions,
+            packs,
+            too_heavy_to_swim: terrain_effects::too_heavy_to_swim(&heavy, &backpacks),
+            chase_maps: HashMap::new(),
+            claimed_spots: HashSet::new(),
+        };
         for (entity, viewshed, _monster, name, pos) in
             (&entities, &mut viewshed, &monster, &name, &mut position).join()
         {
-            let is_confused = confusion.get_mut(entity);
-            let can_act = if let Some(is_confused) = is_confused {
-                is_confused.turns -= 1;
-                console::log(
-                    format!("{} is still confused for {} turns",
-                    name.name,
-                    is_confused.turns,)
-                );
-                if is_confused.turns < 1 {
-                    confusion.remove(entity);
-                    true
-                } else {
-                    false
+            turn.take(entity, name, viewshed, pos);
+            // Hasted monsters get a second go, if they didn't spend their first attacking
+            if status_effects::is_under(&turn.afflictions, entity, StatusKind::Haste)
+                && turn.wants_to_melee.get(entity).is_none()
+                && turn.wants_to_use.get(entity).is_none()
+            {
+                // Look again first, as the first go may have moved it
+                if viewshed.dirty {
+                    let blind =
+                        status_effects::is_under(&turn.afflictions, entity, StatusKind::Blindness);
+                    visibility::refresh_viewshed(&turn.map, viewshed, pos, blind);
                 }
+                turn.take(entity, name, viewshed, pos);
+            }
+        }
+    }
+}
+
+/// Everything a monster's turn reads and changes, besides the monster itself.
+struct Turn<'a> {
+    map: WriteExpect<'a, Map>,
+    rng: WriteExpect<'a, RandomNumberGenerator>,
+    dijkstra: WriteExpect<'a, DijkstraCache>,
+    faction_table: ReadExpect<'a, FactionTable>,
+    factions: ReadStorage<'a, Faction>,
+    combat_stats: ReadStorage<'a, CombatStats>,
+    ai_states: WriteStorage<'a, AiState>,
+    abilities: WriteStorage<'a, Ability>,
+    wants_to_melee: WriteStorage<'a, WantsToMelee>,
+    wants_to_use: WriteStorage<'a, WantsToUseItem>,
+    keeps_distance: ReadStorage<'a, KeepsDistance>,
+    ranged: ReadStorage<'a, Ranged>,
+    healing: ReadStorage<'a, ProvidesHealing>,
+    area_of_effect: ReadStorage<'a, AreaOfEffect>,
+    pack_members: ReadStorage<'a, PackMember>,
+    damage: ReadStorage<'a, InflictsDamage>,
+    inflicts_status: ReadStorage<'a, InflictsStatus>,
+    afflictions: Afflictions,
+    known_abilities: HashMap<Entity, Vec<Entity>>,
+    potions: HashMap<Entity, Entity>,
+    packs: HashMap<Entity, Vec<(Entity, Point)>>,
+    too_heavy_to_swim: HashSet<Entity>,
+    /// Monsters chasing the same target share one map for the turn. It only follows the terrain,
+    /// so it stays right as they move; `downhill_step` steers them around each other.
+    chase_maps: HashMap<usize, DijkstraMap>,
+    claimed_spots: HashSet<usize>,
+}
+
+impl<'a> Turn<'a> {
+    /// One go for one monster: drink, use an ability, attack, or take a step.
+    fn take(&mut self, entity: Entity, name: &Name, viewshed: &mut Viewshed, pos: &mut Position) {
+        let Turn {
+            ref mut map,
+            ref mut rng,
+            ref mut dijkstra,
+            ref faction_table,
+            ref factions,
+            ref combat_stats,
+            ref mut ai_states,
+            ref mut abilities,
+            ref mut wants_to_melee,
+            ref mut wants_to_use,
+            ref keeps_distance,
+            ref ranged,
+            ref healing,
+            ref area_of_effect,
+            ref pack_members,
+            ref damage,
+            ref inflicts_status,
+            ref afflictions,
+            ref known_abilities,
+            ref potions,
+            ref packs,
+            ref too_heavy_to_swim,
+            ref mut chase_maps,
+            ref mut claimed_spots,
+        } = *self;
+
+        let can_act = status_effects::monster_can_act(afflictions, entity);
+        let faction = match factions.get(entity) {
+            Some(faction) if can_act => faction,
+            _ => return,
+        };
+
+        // Monsters only go after enemies they've noticed
+        let aware = match ai_states.get(entity) {
+            Some(AiState::Asleep) => return,
+            Some(AiState::Idle) => false,
+            _ => true,
+        };
+
+        let badly_hurt = combat_stats
+            .get(entity)
+            .map_or(false, |stats| stats.hp * 2 < stats.max_hp);
+        if let (true, Some(potion)) = (badly_hurt, potions.get(&entity)) {
+            wants_to_use
+                .insert(
+                    entity,
+                    WantsToUseItem {
+                        item: *potion,
+                        target: None,
+                    },
+                )
+                .expect("Unable to insert intent");
+            return;
+        }
+
+        let here = Point::new(pos.x, pos.y);
+        let surroundings = look_around(
+            map,
+            faction_table,
+            factions,
+            combat_stats,
+            entity,
+            &faction.name,
+            here,
+            viewshed,
+        );
+
+        let leader = pack_members
+            .get(entity)
+            .map_or(entity, |member| member.leader);
+        let mates: Vec<Point> = packs.get(&leader).map_or(Vec::new(), |pack| {
+            pack.iter()
+                .filter(|(mate, _pos)| *mate != entity)
+                .map(|(_mate, pos)| *pos)
+                .collect()
+        });
+
+        let idx = map.xy_idx(pos.x, pos.y);
+        let afraid = status_effects::is_under(afflictions, entity, StatusKind::Fear);
+        let step = if afraid || ai_states.get(entity) == Some(&AiState::Fleeing) {
+            let mut dangers = surroundings.threats.clone();
+            for (_enemy, enemy_idx, _distance) in surroundings.enemies.iter() {
+                dangers.push(*enemy_idx);
+            }
+            let escape = if dangers.is_empty() {
+                None
             } else {
-                true
+                dijkstra.away_from(map, &dangers).downhill_step(map, idx)
+            };
+
+            // Cornered, it fights back
+            match (escape, surroundings.enemies.first()) {
+                (None, Some((target, _idx, distance))) if *distance < 1.5 => {
+                    wants_to_melee
+                        .insert(entity, WantsToMelee { target: *target })
+                        .expect("Unable to insert attack");
+                    return;
+                }
+                _ => escape,
+            }
+        } else if let Some(&(target, target_idx, distance)) =
+            surroundings.enemies.first().filter(|_enemy| aware)
+        {
+            let last_seen = map.pos_from_idx(target_idx);
+            let hunting = AiState::Hunting {
+                x: last_seen.x,
+                y: last_seen.y,
             };
+            if let Some(state) = ai_states.get_mut(entity) {
+                *state = hunting.clone();
+            }
+            // Call the rest of the pack in, unless they're busy with something else
+            for (mate, _pos) in packs[&leader].iter() {
+                if let Some(state) = ai_states.get_mut(*mate) {
+                    if let AiState::Idle | AiState::Searching { .. } = state {
+                        *state = hunting.clone();
+                    }
+                }
+            }
 
-            if can_act && viewshed.visible_tiles.contains(&*player_pos) {
-                let distance =
-                    rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
+            let target_pos = map.pos_from_idx(target_idx);
+            let target_pos = Point::new(target_pos.x, target_pos.y);
+            let known = known_abilities
+                .get(&entity)
+                .map_or(&[][..], |known| &known[..]);
+            let chosen = choose_ability(
+                map,
+                known,
+                abilities,
+                ranged,
+                healing,
+                damage,
+                area_of_effect,
+                inflicts_status,
+                afflictions,
+                combat_stats,
+                here,
+                &surroundings.allies,
+                (target, target_pos),
+            );
+            if let Some((ability, aim)) = chosen {
+                wants_to_use
+                    .insert(
+                        entity,
+                        WantsToUseItem {
+                            item: ability,
+                            target: Some(aim),
+                        },
+                    )
+                    .expect("Unable to insert intent");
+                if let Some(ability) = abilities.get_mut(ability) {
+                    ability.ready_in = ability.cooldown;
+                }
+                return;
+            }
+
+            let keep_away = keeps_distance
+                .get(entity)
+                .map_or(0.0, |keeps| keeps.distance as f32);
+            let backing_off = if distance < keep_away {
+                dijkstra
+                    .away_from(map, &[target_idx])
+                    .downhill_step(map, idx)
+            } else {
+                None
+            };
+
+            if backing_off.is_some() {
+                backing_off
+            } else {
                 if distance <= 2.0 {
                     console::log(&format!("{} shouts insults", name.name));
                 }
 
                 if distance < 1.5 {
                     wants_to_melee
-                        .insert(
-                            entity,
-                            WantsToMelee {
-                                target: *player_entity,
-                            },
-                        )
+                        .insert(entity, WantsToMelee { target })
                         .expect("Unable to insert attack");
                     return;
                 }
 
-                let start = map.point2d_to_index(Point::new(pos.x, pos.y));
-                let end = map.point2d_to_index(Point::new(player_pos.x, player_pos.y));
-                let path = rltk::a_star_search(start, end, &*map);
+                // Keepers of distance wait for their abilities once they have a clear shot
+                let close_enough = keep_away > 0.0 && distance <= keep_away + 2.0;
+                if close_enough && clear_shot(map, here, target_pos) {
+                    return;
+                }
+
+                let pack_move = if mates.is_empty() {
+                    None
+                } else {
+                    pack_step(
+                        map,
+                        dijkstra,
+                        claimed_spots,
+                        idx,
+                        here,
+                        &mates,
+                        target_idx,
+                        distance,
+                    )
+                };
+                pack_move.or_else(|| {
+                    chase_maps
+                        .entry(target_idx)
+                        .or_insert_with(|| dijkstra.towards(map, &[target_idx]).clone())
+                        .downhill_step(map, idx)
+                })
+            }
+        } else if let Some(state) = ai_states.get_mut(entity) {
+            follow_memory(map, dijkstra, chase_maps, rng, state, idx)
+        } else {
+            None
+        };
+
+        let step = step.filter(|step| {
+            map.tiles[*step] != TileType::DeepWater || !too_heavy_to_swim.contains(&entity)
+        });
+        let wading = map.tiles[idx] == TileType::ShallowWater && rng.roll_dice(1, 2) == 1;
+        if let (Some(step), false) = (step, wading) {
+            map.blocked_tiles[idx] = false;
+
+            let new_pos = map.pos_from_idx(step);
+            pos.x = new_pos.x;
+            pos.y = new_pos.y;
+            let new_idx = map.xy_idx(pos.x, pos.y);
+            map.blocked_tiles[new_idx] = true;
 
-                if path.success && path.steps.len() > 1 {
-                    let idx = map.xy_idx(pos.x, pos.y);
-                    map.blocked_tiles[idx] = false;
+            viewshed.dirty = true;
+        }
+    }
+}
 
-                    let new_pos = map.pos_from_idx(path.steps[1]);
-                    pos.x = new_pos.x;
-                    pos.y = new_pos.y;
-                    let new_idx = map.xy_idx(pos.x, pos.y);
-                    map.blocked_tiles[new_idx] = true;
+/// Everyone a creature can see, sorted by how its faction feels about them.
+pub struct Surroundings {
+    /// Creatures to attack, nearest first, with the tile each stands on and how far away it is.
+    pub enemies: Vec<(Entity, usize, f32)>,
+    /// Tiles holding creatures to run from.
+    pub threats: Vec<usize>,
+    /// Creatures on the same side, the looker included.
+    pub allies: Vec<(Entity, Point)>,
+}
 
-                    viewshed.dirty = true;
+pub fn look_around<S: GenericReadStorage<Component = CombatStats>>(
+    map: &Map,
+    faction_table: &FactionTable,
+    factions: &ReadStorage<Faction>,
+    combat_stats: &S,
+    entity: Entity,
+    faction: &str,
+    here: Point,
+    viewshed: &Viewshed,
+) -> Surroundings {
+    let mut surroundings = Surroundings {
+        enemies: Vec::new(),
+        threats: Vec::new(),
+        allies: vec![(entity, here)],
+    };
+    for tile in viewshed.visible_tiles.iter() {
+        let tile_idx = map.xy_idx(tile.x, tile.y);
+        for other in map.tile_content[tile_idx].iter() {
+            let alive = combat_stats.get(*other).map_or(false, |stats| stats.hp > 0);
+            let other_faction = match factions.get(*other) {
+                Some(other_faction) if alive && *other != entity => other_faction,
+                _ => continue,
+            };
+            if other_faction.name == faction {
+                surroundings.allies.push((*other, *tile));
+            }
+            match faction_table.reaction(faction, &other_faction.name) {
+                Reaction::Attack => {
+                    let distance = rltk::DistanceAlg::Pythagoras.distance2d(here, *tile);
+                    surroundings.enemies.push((*other, tile_idx, distance));
                 }
+                Reaction::Flee => surroundings.threats.push(tile_idx),
+                Reaction::Ignore => {}
+            }
+        }
+    }
+    surroundings
+        .enemies
+        .sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+    surroundings
+}
+
+/// A pack member's move towards its target. One that has strayed from the pack goes back to it
+/// first; otherwise it heads for a free spot around the target that no pack mate has claimed this
+/// turn, so the pack surrounds the target rather than lining up.
+fn pack_step(
+    map: &Map,
+    dijkstra: &mut DijkstraCache,
+    claimed_spots: &mut HashSet<usize>,
+    idx: usize,
+    here: Point,
+    mates: &[Point],
+    target_idx: usize,
+    distance: f32,
+) -> Option<usize> {
+    let target_pos = map.pos_from_idx(target_idx);
+    let target_pos = Point::new(target_pos.x, target_pos.y);
+    let engaged = mates
+        .iter()
+        .any(|mate| rltk::DistanceAlg::Pythagoras.distance2d(*mate, target_pos) < 1.5);
+    let gathered = mates
+        .iter()
+        .filter(|mate| rltk::DistanceAlg::Pythagoras.distance2d(here, **mate) <= PACK_GATHER_RANGE)
+        .count();
+    if !engaged && distance > PACK_ENGAGE_RANGE && gathered < usize::min(mates.len(), 2) {
+        let mate_tiles: Vec<usize> = mates
+            .iter()
+            .map(|mate| map.xy_idx(mate.x, mate.y))
+            .collect();
+        let regroup = dijkstra.towards(map, &mate_tiles).downhill_step(map, idx);
+        if regroup.is_some() {
+            return regroup;
+        }
+    }
+
+    let reach = dijkstra.towards(map, &[idx]);
+    let spot = map
+        .get_available_exits(target_idx)
+        .iter()
+        .map(|(exit, _cost)| *exit)
+        .filter(|exit| !claimed_spots.contains(exit) && reach.is_reachable(*exit))
+        .min_by(|a, b| {
+            reach.distances[*a]
+                .partial_cmp(&reach.distances[*b])
+                .unwrap()
+        })?;
+    claimed_spots.insert(spot);
+    dijkstra.towards(map, &[spot]).downhill_step(map, idx)
+}
+
+/// Where a monster with no enemy in sight goes next: to the spot it last saw one, then around
+/// that spot for a while, and then nowhere.
+fn follow_memory(
+    map: &Map,
+    dijkstra: &mut DijkstraCache,
+    chase_maps: &mut HashMap<usize, DijkstraMap>,
+    rng: &mut RandomNumberGenerator,
+    state: &mut AiState,
+    idx: usize,
+) -> Option<usize> {
+    match *state {
+        AiState::Asleep | AiState::Idle | AiState::Fleeing => None,
+        AiState::Hunting { x, y } => {
+            let last_seen = map.xy_idx(x, y);
+            let step = chase_maps
+                .entry(last_seen)
+                .or_insert_with(|| dijkstra.towards(map, &[last_seen]).clone())
+                .downhill_step(map, idx);
+            if step.is_none() {
+                *state = AiState::Searching {
+                    x,
+                    y,
+                    turns: SEARCH_TURNS,
+                };
+            }
+            step
+        }
+        AiState::Searching { x, y, turns } => {
+            if turns < 1 {
+                *state = AiState::Idle;
+                return None;
+            }
+            *state = AiState::Searching {
+                x,
+                y,
+                turns: turns - 1,
+            };
+
+            let last_seen = Point::new(x, y);
+            let exits: Vec<usize> = map
+                .get_available_exits(idx)
+                .iter()
+                .map(|(exit, _cost)| *exit)
+                .filter(|exit| {
+                    let pos = map.pos_from_idx(*exit);
+                    let distance = rltk::DistanceAlg::Pythagoras
+                        .distance2d(last_seen, Point::new(pos.x, pos.y));
+                    !map.tiles[*exit].is_hazardous() && distance <= SEARCH_RADIUS
+                })
+                .collect();
+            if exits.is_empty() {
+                None
+            } else {
+                Some(exits[rng.range(0, exits.len() as i32) as usize])
             }
         }
     }
 }
+
+/// Picks a ready ability or carried item and where to aim it: a heal for the worst hurt of the
+/// monster's allies (itself included) when one is badly hurt, otherwise any attack that reaches
+/// the enemy. Area attacks aren't thrown where they would catch an ally, and status effects aren't
+/// wasted on an enemy already under them.
+fn choose_ability(
+    map: &Map,
+    known: &[Entity],
+    abilities: &WriteStorage<Ability>,
+    ranged: &ReadStorage<Ranged>,
+    healing: &ReadStorage<ProvidesHealing>,
+    damage: &ReadStorage<InflictsDamage>,
+    area_of_effect: &ReadStorage<AreaOfEffect>,
+    inflicts_status: &ReadStorage<InflictsStatus>,
+    afflictions: &Afflictions,
+    combat_stats: &ReadStorage<CombatStats>,
+    here: Point,
+    allies: &[(Entity, Point)],
+    enemy: (Entity, Point),
+) -> Option<(Entity, Point)> {
+    let in_reach = |ability: Entity, target: Point| {
+        let range = ranged
+            .get(ability)
+            .map_or(1.5, |ranged| ranged.range as f32);
+        rltk::DistanceAlg::Pythagoras.distance2d(here, target) <= range
+            && clear_shot(map, here, target)
+    };
+    let ready: Vec<Entity> = known
+        .iter()
+        .filter(|ability| abilities.get(**ability).map_or(true, |a| a.ready_in < 1))
+        .cloned()
+        .collect();
+
+    let badly_hurt = allies
+        .iter()
+        .filter_map(|(ally, pos)| combat_stats.get(*ally).map(|stats| (stats, *pos)))
+        .filter(|(stats, _pos)| stats.hp * 2 < stats.max_hp)
+        .min_by_key(|(stats, _pos)| stats.hp * 100 / stats.max_hp);
+    if let Some((_stats, ally_pos)) = badly_hurt {
+        let heal = ready
+            .iter()
+            .find(|ability| healing.get(**ability).is_some() && in_reach(**ability, ally_pos));
+        if let Some(heal) = heal {
+            return Some((*heal, ally_pos));
+        }
+    }
+
+    let (enemy, enemy_pos) = enemy;
+    ready
+        .iter()
+        .filter(|ability| {
+            let status = inflicts_status.get(**ability);
+            let harmful = status.map_or(false, |status| status.kind.is_harmful());
+            damage.get(**ability).is_some() || harmful
+        })
+        .filter(|ability| {
+            inflicts_status.get(**ability).map_or(true, |status| {
+                !status_effects::is_under(afflictions, enemy, status.kind)
+            })
+        })
+        .filter(|ability| {
+            area_of_effect.get(**ability).map_or(true, |area| {
+                allies.iter().all(|(_ally, pos)| {
+                    rltk::DistanceAlg::Pythagoras.distance2d(*pos, enemy_pos)
+                        > area.radius as f32 + 0.5
+                })
+            })
+        })
+        .find(|ability| in_reach(**ability, enemy_pos))
+        .map(|ability| (*ability, enemy_pos))
+}
+
+/// Whether nothing stands between `from` and `to`: no walls, and nobody in the way.
+fn clear_shot(map: &Map, from: Point, to: Point) -> bool {
+    let line = geometry::line(from, to);
+    let between = line.len().saturating_sub(2);
+    line.iter().skip(1).take(between).all(|point| {
+        let idx = map.xy_idx(point.x, point.y);
+        !map.tiles[idx].is_opaque() && !map.blocked_tiles[idx]
+    })
+}
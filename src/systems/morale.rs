@@ -0,0 +1,147 @@
+use super::{look_around, status_effects};
+use crate::factions::FactionTable;
+use crate::{
+    AiState, CombatStats, Faction, GameLog, Map, Monster, Morale, Name, Position, RunState,
+    StatusEffect, Viewshed,
+};
+use rltk::Point;
+use specs::prelude::*;
+
+/// Decides which monsters lose their nerve and which get it back, before they act. A monster runs
+/// when it sees something its faction fears, when it's outnumbered, when it's badly hurt, or, if
+/// it's a coward, as soon as it sees an enemy. Once none of that holds it regains its courage.
+/// Shaken monsters, such as a pack that just lost its leader, run regardless for a while.
+/// Fleeing monsters out of sight of danger catch their breath and slowly heal. Monsters that are
+/// asleep or stunned are left as they are until they come round.
+pub struct MoraleCheck {}
+
+impl<'a> System<'a> for MoraleCheck {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Point>,
+        ReadExpect<'a, FactionTable>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Faction>,
+        WriteStorage<'a, Morale>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, AiState>,
+        ReadStorage<'a, StatusEffect>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            run_state,
+            player_pos,
+            faction_table,
+            mut gamelog,
+            entities,
+            monsters,
+            names,
+            positions,
+            viewsheds,
+            factions,
+            mut morale,
+            mut combat_stats,
+            mut ai_states,
+            effects,
+        ) = data;
+
+        if *run_state != RunState::MonsterTurn {
+            return;
+        }
+
+        let afflictions = status_effects::afflictions(&effects);
+        let mut changes: Vec<(Entity, bool)> = Vec::new();
+        let mut resting: Vec<Entity> = Vec::new();
+        for (entity, _monster, pos, viewshed, faction, state, mut morale) in (
+            &entities,
+            &monsters,
+            &positions,
+            &viewsheds,
+            &factions,
+            &ai_states,
+            (&mut morale).maybe(),
+        )
+            .join()
+        {
+            // Shaken monsters get over it in the same time whatever else is going on
+            let shaken = match morale {
+                Some(ref mut morale) if morale.shaken > 0 => {
+                    morale.shaken -= 1;
+                    true
+                }
+                _ => false,
+            };
+
+            // Nothing frightens a monster that hasn't noticed anyone
+            if let AiState::Asleep | AiState::Idle = state {
+                continue;
+            }
+            if status_effects::is_helpless(&afflictions, entity) {
+                continue;
+            }
+            let here = Point::new(pos.x, pos.y);
+            let surroundings = look_around(
+                &map,
+                &faction_table,
+                &factions,
+                &combat_stats,
+                entity,
+                &faction.name,
+                here,
+                viewshed,
+            );
+
+            let stats = match combat_stats.get(entity) {
+                Some(stats) => stats,
+                None => continue,
+            };
+            let enemies_in_sight = !surroundings.enemies.is_empty();
+            let frightened = shaken
+                || !surroundings.threats.is_empty()
+                || morale.map_or(false, |morale| {
+                    (morale.cowardly && enemies_in_sight)
+                        || stats.hp * 100 < stats.max_hp * morale.flee_below
+                        || surroundings.enemies.len() > surroundings.allies.len()
+                });
+
+            let fleeing = *state == AiState::Fleeing;
+            if frightened != fleeing {
+                changes.push((entity, frightened));
+            } else if fleeing && !enemies_in_sight && surroundings.threats.is_empty() {
+                resting.push(entity);
+            }
+        }
+
+        for entity in resting {
+            if let Some(stats) = combat_stats.get_mut(entity) {
+                stats.hp = i32::min(stats.max_hp, stats.hp + 1);
+            }
+        }
+
+        for (entity, frightened) in changes {
+            let seen = positions.get(entity).map_or(false, |pos| {
+                map.can_make_out(map.xy_idx(pos.x, pos.y), *player_pos)
+            });
+            let name = names.get(entity).map_or("monster", |name| &name.name);
+            let (state, message) = if frightened {
+                (AiState::Fleeing, format!("The {} turns to flee!", name))
+            } else {
+                (AiState::Idle, format!("The {} regains its courage.", name))
+            };
+            ai_states
+                .insert(entity, state)
+                .expect("Unable to insert AI state");
+            if seen {
+                gamelog.entries.push(message);
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{Map, Position, RunState, Wanderer};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+pub struct NpcAI {}
+
+impl<'a> System<'a> for NpcAI {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadStorage<'a, Wanderer>,
+        WriteStorage<'a, Position>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, run_state, mut rng, wanderers, mut positions) = data;
+
+        if *run_state != RunState::MonsterTurn {
+            return;
+        }
+
+        for (_wanderer, pos) in (&wanderers, &mut positions).join() {
+            let (delta_x, delta_y) = match rng.roll_dice(1, 8) {
+                1 => (-1, 0),
+                2 => (1, 0),
+                3 => (0, -1),
+                4 => (0, 1),
+                _ => (0, 0), // Idle most of the time
+            };
+            if delta_x == 0 && delta_y == 0 {
+                continue;
+            }
+
+            let (destination_x, destination_y) = (pos.x + delta_x, pos.y + delta_y);
+            if !map.is_floor_available(destination_x, destination_y)
+                || map.tiles[map.xy_idx(destination_x, destination_y)].is_hazardous()
+            {
+                continue;
+            }
+
+            let idx = map.xy_idx(pos.x, pos.y);
+            map.blocked_tiles[idx] = false;
+            pos.x = destination_x;
+            pos.y = destination_y;
+            let new_idx = map.xy_idx(pos.x, pos.y);
+            map.blocked_tiles[new_idx] = true;
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    CombatStats, DamageType, GameLog, RunState, StatusEffect, StatusKind, SufferDamage, Viewshed,
+};
+use specs::prelude::*;
+use std::collections::HashMap;
+
+/// Every creature under a status effect, with the effects it's under and how many turns each
+/// has left.
+pub type Afflictions = HashMap<Entity, Vec<(StatusKind, i32)>>;
+
+pub fn afflictions(effects: &ReadStorage<StatusEffect>) -> Afflictions {
+    let mut afflictions = Afflictions::new();
+    for effect in effects.join() {
+        afflictions
+            .entry(effect.target)
+            .or_insert_with(Vec::new)
+            .push((effect.kind, effect.turns));
+    }
+    afflictions
+}
+
+/// How many turns `entity` is still under `kind` for, if it is.
+pub fn turns_under(afflictions: &Afflictions, entity: Entity, kind: StatusKind) -> Option<i32> {
+    afflictions
+        .get(&entity)?
+        .iter()
+        .find(|(affliction, _turns)| *affliction == kind)
+        .map(|(_affliction, turns)| *turns)
+}
+
+pub fn is_under(afflictions: &Afflictions, entity: Entity, kind: StatusKind) -> bool {
+    turns_under(afflictions, entity, kind).is_some()
+}
+
+/// Whether `entity` is in no state to do anything at all.
+pub fn is_helpless(afflictions: &Afflictions, entity: Entity) -> bool {
+    afflictions.get(&entity).map_or(false, |effects| {
+        effects.iter().any(|(kind, _turns)| kind.incapacitates())
+    })
+}
+
+/// Whether a monster gets to do anything this turn: not while it's asleep, stunned or confused,
+/// and only every other turn while it's slowed.
+pub fn monster_can_act(afflictions: &Afflictions, entity: Entity) -> bool {
+    afflictions.get(&entity).map_or(true, |effects| {
+        effects.iter().all(|(kind, turns)| match kind {
+            StatusKind::Confusion => false,
+            StatusKind::Slow => turns % 2 == 1,
+            _ => !kind.incapacitates(),
+        })
+    })
+}
+
+/// Runs every status effect once each turn of the creature under it: poison hurts and regeneration
+/// heals, blindness keeps its victim's view narrowed, and effects run down and wear off. A fresh
+/// dose of an effect a creature is already under replaces the old one if it lasts longer.
+pub struct StatusEffects {}
+
+impl<'a> System<'a> for StatusEffects {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, StatusEffect>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            run_state,
+            player_entity,
+            mut gamelog,
+            mut effects,
+            mut combat_stats,
+            mut suffer_damage,
+            mut viewsheds,
+        ) = data;
+
+        // Effects on the player run with the player's turns, so haste and slow don't stretch or
+        // shorten them, and everyone else's with the monsters' turns
+        let players_turn = match *run_state {
+            RunState::PlayerTurn => true,
+            RunState::MonsterTurn => false,
+            _ => return,
+        };
+
+        let mut strongest: HashMap<(Entity, StatusKind), (Entity, i32)> = HashMap::new();
+        let mut superseded = Vec::new();
+        for (entity, effect) in (&entities, &effects).join() {
+            let key = (effect.target, effect.kind);
+            match strongest.get(&key) {
+                Some(&(_other, turns)) if turns >= effect.turns => superseded.push(entity),
+                Some(&(other, _turns)) => {
+                    superseded.push(other);
+                    strongest.insert(key, (entity, effect.turns));
+                }
+                None => {
+                    strongest.insert(key, (entity, effect.turns));
+                }
+            }
+        }
+        for entity in superseded {
+            effects.remove(entity);
+            entities
+                .delete(entity)
+                .expect("Unable to delete status effect");
+        }
+
+        let mut worn_off = Vec::new();
+        for (entity, effect) in (&entities, &mut effects).join() {
+            let target = effect.target;
+            if (target == *player_entity) != players_turn {
+                continue;
+            }
+            match effect.kind {
+                StatusKind::Poison => {
+                    SufferDamage::new_damage(
+                        &mut suffer_damage,
+                        target,
+                        effect.potency,
+                        DamageType::Poison,
+                        false,
+                    );
+                    if target == *player_entity {
+                        gamelog.entries.push(format!(
+                            "The poison burns in your veins, for {} hp.",
+                            effect.potency
+                        ));
+                    }
+                }
+                StatusKind::Regeneration => {
+                    if let Some(stats) = combat_stats.get_mut(target) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + effect.potency);
+                    }
+                }
+                // Checked every turn, so the view widens again the moment it wears off
+                StatusKind::Blindness => {
+                    if let Some(viewshed) = viewsheds.get_mut(target) {
+                        viewshed.dirty = true;
+                    }
+                }
+                _ => {}
+            }
+
+            effect.turns -= 1;
+            if effect.turns < 1 {
+                worn_off.push(entity);
+                if target == *player_entity {
+                    gamelog.entries.push(format!(
+                        "You are no longer {}.",
+                        effect.kind.name().to_lowercase()
+                    ));
+                }
+            }
+        }
+        for entity in worn_off {
+            effects.remove(entity);
+            entities
+                .delete(entity)
+                .expect("Unable to delete status effect");
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{
+    Burning, CombatStats, DamageType, GameLog, Heavy, InBackpack, Map, Position, RunState,
+    SufferDamage, TileType,
+};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use std::collections::HashSet;
+
+const LAVA_BURN_TURNS: i32 = 3;
+
+/// Everyone carrying something `Heavy`, who can't swim and so can't go into deep water.
+pub fn too_heavy_to_swim(
+    heavy: &ReadStorage<Heavy>,
+    backpacks: &ReadStorage<InBackpack>,
+) -> HashSet<Entity> {
+    (heavy, backpacks)
+        .join()
+        .map(|(_item, carried)| carried.owner)
+        .collect()
+}
+
+/// Applies the terrain everyone is standing on at the end of each round: lava sets them alight,
+/// water puts the flames out, and burning hurts.
+pub struct TerrainEffects {}
+
+impl<'a> System<'a> for TerrainEffects {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, Burning>,
+        WriteStorage<'a, SufferDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            run_state,
+            player_entity,
+            mut gamelog,
+            mut rng,
+            entities,
+            positions,
+            combat_stats,
+            mut burning,
+            mut suffer_damage,
+        ) = data;
+
+        if *run_state != RunState::MonsterTurn {
+            return;
+        }
+
+        for (entity, pos, _stats) in (&entities, &positions, &combat_stats).join() {
+            let tile = &map.tiles[map.xy_idx(pos.x, pos.y)];
+            if *tile == TileType::Lava {
+                if burning.get(entity).is_none() && entity == *player_entity {
+                    gamelog
+                        .entries
+                        .push("The lava sets you alight!".to_string());
+                }
+                burning
+                    .insert(
+                        entity,
+                        Burning {
+                            turns: LAVA_BURN_TURNS,
+                        },
+                    )
+                    .expect("Unable to insert burning");
+            } else if tile.is_water() && burning.remove(entity).is_some() {
+                if entity == *player_entity {
+                    gamelog
+                        .entries
+                        .push("The water puts out the flames.".to_string());
+                }
+            }
+        }
+
+        let mut burnt_out = Vec::new();
+        for (entity, burning) in (&entities, &mut burning).join() {
+            let damage = rng.roll_dice(1, 4);
+            SufferDamage::new_damage(&mut suffer_damage, entity, damage, DamageType::Fire, false);
+            if entity == *player_entity {
+                gamelog
+                    .entries
+                    .push(format!("You burn, for {} hp.", damage));
+            }
+
+            burning.turns -= 1;
+            if burning.turns < 1 {
+                burnt_out.push(entity);
+            }
+        }
+        for entity in burnt_out {
+            burning.remove(entity);
+        }
+    }
+}
@@ -1,7 +1,13 @@
-use crate::{Map, Player, Position, Viewshed};
+use super::status_effects;
+use crate::{Map, Player, Position, StatusEffect, StatusKind, Viewshed};
 use rltk::{field_of_view, Point};
 use specs::prelude::*;
 
+/// How far away the player can spot lit tiles, well beyond their own sight radius.
+const LIT_VIEW_RANGE: i32 = 24;
+/// How far a blinded creature can see: no further than it can reach.
+const BLIND_VIEW_RANGE: i32 = 1;
+
 pub struct Visibility {}
 
 impl<'a> System<'a> for Visibility {
@@ -11,23 +17,37 @@ impl<'a> System<'a> for Visibility {
         WriteStorage<'a, Viewshed>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Player>,
+        ReadStorage<'a, StatusEffect>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, effects) = data;
+        let afflictions = status_effects::afflictions(&effects);
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
-                viewshed.dirty = false;
-                viewshed.visible_tiles.clear();
-                viewshed.visible_tiles =
-                    field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
-                viewshed
-                    .visible_tiles
-                    .retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+                let blind = status_effects::is_under(&afflictions, ent, StatusKind::Blindness);
+                refresh_viewshed(&map, viewshed, pos, blind);
 
-                // If this is the player, reveal what they can see
+                // If this is the player, reveal what they can see, including lit areas further off
+                // unless they're blinded
                 let p: Option<&Player> = player.get(ent);
                 if p.is_some() {
+                    if !blind {
+                        let lit_tiles =
+                            field_of_view(Point::new(pos.x, pos.y), LIT_VIEW_RANGE, &*map);
+                        for tile in lit_tiles {
+                            if tile.x > 0
+                                && tile.x < map.width - 1
+                                && tile.y > 0
+                                && tile.y < map.height - 1
+                                && map.is_lit(map.xy_idx(tile.x, tile.y))
+                                && !viewshed.visible_tiles.contains(&tile)
+                            {
+                                viewshed.visible_tiles.push(tile);
+                            }
+                        }
+                    }
+
                     for t in map.visible_tiles.iter_mut() {
                         *t = false
                     }
@@ -41,3 +61,17 @@ impl<'a> System<'a> for Visibility {
         }
     }
 }
+
+/// Works out what a creature standing at `pos` can see now, leaving the map alone.
+pub fn refresh_viewshed(map: &Map, viewshed: &mut Viewshed, pos: &Position, blind: bool) {
+    viewshed.dirty = false;
+    let range = if blind {
+        BLIND_VIEW_RANGE
+    } else {
+        viewshed.range
+    };
+    viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), range, map);
+    viewshed
+        .visible_tiles
+        .retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+}